@@ -0,0 +1,118 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use super::{
+    examples::append_line,
+    guard::Finding,
+    router::{Route, RouteMethod},
+};
+use crate::config::AuditConfig;
+
+const FILE_NAME: &str = "audit.jsonl";
+
+/// Acción del agente registrada en el log de auditoría
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(super) enum AuditEvent<'a> {
+    Query {
+        question: &'a str,
+        prompt_hash: &'a str,
+        sql: &'a str,
+        blocked: bool,
+        block_reason: Option<&'a str>,
+        rows: Option<u64>,
+        /// El resultado salió de la caché y la query no se ejecutó
+        cached: bool,
+        duration_ms: u128,
+        error: Option<String>,
+    },
+    Answer {
+        question: &'a str,
+        answer: &'a str,
+    },
+    Route {
+        question: &'a str,
+        route: Route,
+        method: RouteMethod,
+    },
+    /// Valores del resultado con aspecto de instrucciones
+    Injection {
+        question: &'a str,
+        sql: &'a str,
+        findings: &'a [Finding],
+        redacted: bool,
+    },
+}
+
+/// Log JSONL de solo escritura con rotación por tamaño
+pub(super) struct AuditLog {
+    enabled: bool,
+    dir: PathBuf,
+    max_bytes: u64,
+    session_id: String,
+    database: String,
+}
+
+impl AuditLog {
+    pub fn new(cfg: &AuditConfig, session_id: &str, database: &str) -> Self {
+        Self {
+            enabled: cfg.enabled,
+            dir: PathBuf::from(&cfg.dir),
+            max_bytes: cfg.max_bytes,
+            session_id: session_id.to_string(),
+            database: database.to_string(),
+        }
+    }
+
+    pub fn log(&self, event: AuditEvent) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let mut record = json!({
+            "timestamp": SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64,
+            "session_id": self.session_id,
+            "database": self.database,
+        });
+        if let (Some(record), serde_json::Value::Object(fields)) =
+            (record.as_object_mut(), serde_json::to_value(&event)?)
+        {
+            record.extend(fields);
+        }
+        let line = serde_json::to_string(&record)?;
+
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(FILE_NAME);
+        self.rotate(&path, line.len() as u64)?;
+        append_line(&path, &line)
+    }
+
+    /// Renombra el fichero actual si la nueva línea supera el tamaño máximo
+    fn rotate(&self, path: &PathBuf, incoming: u64) -> Result<()> {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return Ok(());
+        };
+
+        if metadata.len() + incoming > self.max_bytes {
+            let stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+            std::fs::rename(path, self.dir.join(format!("audit-{}.jsonl", stamp)))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// SHA-256 del prompt renderizado que produjo la query
+pub(super) fn hash(prompt: &str) -> String {
+    Sha256::digest(prompt.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
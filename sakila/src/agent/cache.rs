@@ -0,0 +1,370 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{audit::hash, examples::append_line};
+use crate::config::CacheConfig;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    created_ms: u64,
+    bytes: usize,
+    result: Value,
+}
+
+/// Caché de resultados en disco, compartida entre sesiones. La clave es la
+/// SQL normalizada más la huella de la base de datos: si el fichero cambia,
+/// las entradas antiguas dejan de coincidir.
+///
+/// El fichero es JSONL: cada entrada nueva se añade al final y solo se
+/// reescribe entero cuando se descartan entradas.
+pub(super) struct ResultCache {
+    enabled: bool,
+    path: PathBuf,
+    ttl_ms: u64,
+    max_entries: usize,
+    max_bytes: usize,
+    fingerprint: String,
+    entries: HashMap<String, Entry>,
+}
+
+impl ResultCache {
+    pub fn load(cfg: &CacheConfig, fingerprint: &str) -> Result<Self> {
+        let path = PathBuf::from(&cfg.file);
+        let (entries, lines) = match std::fs::read_to_string(&path) {
+            Ok(content) if cfg.enabled => read_entries(&content),
+            _ => (HashMap::new(), 0),
+        };
+
+        let mut cache = Self {
+            enabled: cfg.enabled,
+            path,
+            ttl_ms: cfg.ttl_secs * 1000,
+            max_entries: cfg.max_entries,
+            max_bytes: cfg.max_bytes,
+            fingerprint: fingerprint.to_string(),
+            entries,
+        };
+        // Entradas caducadas o repetidas en el fichero: se compacta
+        if (cache.evict(now_ms()?) || lines > cache.entries.len()) && cfg.enabled {
+            cache.compact()?;
+        }
+        Ok(cache)
+    }
+
+    pub fn get(&self, sql: &str) -> Option<Value> {
+        if !self.enabled {
+            return None;
+        }
+
+        let entry = self.entries.get(&self.key(sql))?;
+        let fresh = now_ms().ok()? < entry.created_ms + self.ttl_ms;
+        fresh.then(|| entry.result.clone())
+    }
+
+    pub fn insert(&mut self, sql: &str, result: &Value) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let bytes = serde_json::to_string(result)?.len();
+        if bytes > self.max_bytes {
+            return Ok(());
+        }
+
+        let now = now_ms()?;
+        let key = self.key(sql);
+        let entry = Entry {
+            created_ms: now,
+            bytes,
+            result: result.clone(),
+        };
+        let line = serde_json::to_string(&(&key, &entry))?;
+        self.entries.insert(key, entry);
+
+        if self.evict(now) {
+            return self.compact();
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        append_line(&self.path, &line)
+    }
+
+    fn key(&self, sql: &str) -> String {
+        hash(&format!("{}\n{}", self.fingerprint, normalize(sql)))
+    }
+
+    /// Quita las entradas caducadas y después las más antiguas hasta
+    /// respetar los límites de entradas y tamaño. Devuelve si quitó alguna
+    fn evict(&mut self, now: u64) -> bool {
+        let before = self.entries.len();
+        let ttl_ms = self.ttl_ms;
+        self.entries
+            .retain(|_, entry| now < entry.created_ms + ttl_ms);
+
+        let mut total: usize = self.entries.values().map(|entry| entry.bytes).sum();
+        let mut by_age: Vec<(String, u64, usize)> = self
+            .entries
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.created_ms, entry.bytes))
+            .collect();
+        by_age.sort_by_key(|(_, created_ms, _)| *created_ms);
+
+        for (key, _, bytes) in by_age {
+            if self.entries.len() <= self.max_entries && total <= self.max_bytes {
+                break;
+            }
+            self.entries.remove(&key);
+            total -= bytes;
+        }
+
+        self.entries.len() < before
+    }
+
+    /// Reescribe el fichero solo con las entradas vigentes
+    fn compact(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut content = String::new();
+        for record in &self.entries {
+            content.push_str(&serde_json::to_string(&record)?);
+            content.push('\n');
+        }
+        // Escritura atómica: nunca queda un fichero a medias
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, content)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Entradas del fichero (las últimas sustituyen a las anteriores con la
+/// misma clave) y número de líneas leídas
+fn read_entries(content: &str) -> (HashMap<String, Entry>, usize) {
+    let mut entries = HashMap::new();
+    let mut lines = 0;
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        lines += 1;
+        match serde_json::from_str::<(String, Entry)>(line) {
+            Ok((key, entry)) => {
+                entries.insert(key, entry);
+            }
+            // Una línea cortada (p. ej. por un cierre brusco) solo pierde esa entrada
+            Err(err) => tracing::warn!("⚠️  Entrada de caché ilegible: {}", err),
+        }
+    }
+    (entries, lines)
+}
+
+/// Espacios colapsados, palabras clave e identificadores en minúsculas, sin
+/// comentarios y sin `;` final. Los literales de texto y los identificadores
+/// entre comillas (`"..."`, `` `...` ``, `[...]`) se conservan tal cual: dos
+/// queries con valores distintos nunca comparten entrada.
+fn normalize(sql: &str) -> String {
+    let mut normalized = String::with_capacity(sql.len());
+    let mut chars = sql.trim().trim_end_matches(';').chars().peekable();
+    let mut pending_space = false;
+
+    while let Some(c) = chars.next() {
+        // Un comentario separa como un espacio: `-- x\n+ 1` no es `-- x + 1`
+        if c == '-' && chars.peek() == Some(&'-') {
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+            pending_space = true;
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            let mut previous = ' ';
+            for c in chars.by_ref() {
+                if previous == '*' && c == '/' {
+                    break;
+                }
+                previous = c;
+            }
+            pending_space = true;
+        } else if let Some(close) = closing_quote(c) {
+            // Las comillas cuentan como puntuación: sin espacio delante, salvo
+            // tras otras comillas (`'It' 's'` no es `'It''s'`)
+            let after_quote = normalized
+                .chars()
+                .last()
+                .is_some_and(|last| "'\"`]".contains(last));
+            if pending_space && after_quote {
+                normalized.push(' ');
+            }
+            pending_space = false;
+
+            // Texto entre comillas ('' o "" es una comilla escapada)
+            normalized.push(c);
+            while let Some(c) = chars.next() {
+                normalized.push(c);
+                if c == close {
+                    if close != ']' && chars.peek() == Some(&close) {
+                        normalized.push(chars.next().unwrap_or(close));
+                    } else {
+                        break;
+                    }
+                }
+            }
+        } else if c.is_whitespace() {
+            pending_space = true;
+        } else {
+            // Sin espacios alrededor de la puntuación: "a , b" == "a,b". Se
+            // conserva entre dos guiones o barras para no formar un comentario
+            let punctuation = !c.is_alphanumeric() && c != '_';
+            let last = normalized.chars().last();
+            let last_punctuation =
+                last.is_some_and(|last| !last.is_alphanumeric() && !"_'\"`]".contains(last));
+            let forms_comment = matches!((last, c), (Some('-'), '-') | (Some('/'), '*'));
+            if pending_space
+                && !normalized.is_empty()
+                && (forms_comment || (!punctuation && !last_punctuation))
+            {
+                normalized.push(' ');
+            }
+            pending_space = false;
+            normalized.extend(c.to_lowercase());
+        }
+    }
+
+    normalized
+}
+
+/// Cierre de cada tipo de comillas
+fn closing_quote(open: char) -> Option<char> {
+    match open {
+        '\'' | '"' | '`' => Some(open),
+        '[' => Some(']'),
+        _ => None,
+    }
+}
+
+fn now_ms() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(path: &std::path::Path, max_entries: usize) -> ResultCache {
+        let cfg = CacheConfig {
+            enabled: true,
+            file: path.display().to_string(),
+            ttl_secs: 3600,
+            max_entries,
+            max_bytes: 1024 * 1024,
+        };
+        ResultCache::load(&cfg, "sakila").unwrap()
+    }
+
+    fn lines(path: &std::path::Path) -> usize {
+        std::fs::read_to_string(path).unwrap().lines().count()
+    }
+
+    #[test]
+    fn entries_are_appended_and_compacted_on_eviction() {
+        let path =
+            std::env::temp_dir().join(format!("sakila-test-{}-cache.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut results = cache(&path, 2);
+        results.insert("SELECT 1", &serde_json::json!(1)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        results.insert("SELECT 2", &serde_json::json!(2)).unwrap();
+        results.insert("SELECT 2", &serde_json::json!(2)).unwrap();
+        assert_eq!(lines(&path), 3);
+
+        // Al cargar, la línea repetida se compacta
+        let mut results = cache(&path, 2);
+        assert_eq!(results.get("select 1"), Some(serde_json::json!(1)));
+        assert_eq!(lines(&path), 2);
+
+        // La tercera entrada supera el máximo: se descarta la más antigua
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        results.insert("SELECT 3", &serde_json::json!(3)).unwrap();
+        assert_eq!(lines(&path), 2);
+        assert_eq!(cache(&path, 2).get("SELECT 1"), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn case_and_whitespace() {
+        assert_eq!(
+            normalize("  SELECT  title\n\tFROM film ;"),
+            "select title from film"
+        );
+        assert_eq!(
+            normalize("select title from film"),
+            normalize("SELECT title FROM film;")
+        );
+    }
+
+    #[test]
+    fn spaces_around_punctuation() {
+        assert_eq!(
+            normalize("SELECT a , b FROM t WHERE x = ( 1 + 2 )"),
+            "select a,b from t where x=(1+2)"
+        );
+        assert_eq!(normalize("COUNT( * )"), normalize("count(*)"));
+    }
+
+    #[test]
+    fn different_queries_never_share_a_key() {
+        let pairs = [
+            (
+                "SELECT * FROM t WHERE name = 'Ana'",
+                "SELECT * FROM t WHERE name = 'ana'",
+            ),
+            ("SELECT \"Col\" FROM t", "SELECT \"col\" FROM t"),
+            ("SELECT `Col` FROM t", "SELECT `col` FROM t"),
+            ("SELECT [Col] FROM t", "SELECT [col] FROM t"),
+            ("SELECT 1 -- x\n+ 1", "SELECT 1 -- x + 1"),
+            ("SELECT 1 - -1", "SELECT 1 --1"),
+            ("SELECT 4 / *", "SELECT 4 /* */"),
+            ("SELECT 'a -- b'", "SELECT 'a '"),
+            ("SELECT ab FROM t", "SELECT a b FROM t"),
+            ("SELECT 'It''s' FROM t", "SELECT 'It' 's' FROM t"),
+        ];
+        for (a, b) in pairs {
+            assert_ne!(normalize(a), normalize(b), "{} | {}", a, b);
+        }
+    }
+
+    #[test]
+    fn comments_are_whitespace() {
+        assert_eq!(
+            normalize("SELECT title -- título\nFROM film /* todas */ LIMIT 5"),
+            normalize("SELECT title FROM film LIMIT 5")
+        );
+        assert_eq!(normalize("SELECT 1--x"), "select 1");
+    }
+
+    #[test]
+    fn quoted_text_is_kept() {
+        assert_eq!(
+            normalize("SELECT * FROM actor WHERE name = 'Penélope  GUINESS'"),
+            "select*from actor where name='Penélope  GUINESS'"
+        );
+        assert_eq!(
+            normalize("WHERE title = 'It''s  A' AND \"Col  X\" = 1"),
+            "where title='It''s  A' and\"Col  X\"=1"
+        );
+        assert_eq!(
+            normalize("SELECT [My  Col] FROM t"),
+            "select[My  Col] from t"
+        );
+        assert_ne!(normalize("WHERE a = 'x'"), normalize("WHERE a = 'X'"));
+    }
+}
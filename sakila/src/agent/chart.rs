@@ -0,0 +1,482 @@
+use regex::Regex;
+use serde_json::Value;
+
+use super::{digest::value_to_string, guard::escape_markup};
+use crate::t;
+
+const BAR_WIDTH: usize = 40;
+const MAX_BARS: usize = 30;
+const PLOT_HEIGHT: usize = 10;
+const PLOT_WIDTH: usize = 60;
+const SVG_WIDTH: f64 = 800.0;
+const SVG_HEIGHT: f64 = 400.0;
+const SVG_MARGIN: f64 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ChartKind {
+    Bar,
+    Line,
+}
+
+impl ChartKind {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "bar" => Some(Self::Bar),
+            "line" => Some(Self::Line),
+            _ => None,
+        }
+    }
+}
+
+/// Resultado con forma de gráfico: una columna de etiquetas y una numérica
+#[derive(Debug, Clone)]
+pub(super) struct Chart {
+    pub kind: ChartKind,
+    label: String,
+    value: String,
+    points: Vec<(String, f64)>,
+}
+
+/// Tipo de gráfico pedido por el modelo con `<chart>bar</chart>`
+pub(super) fn extract_kind(text: &str) -> Option<ChartKind> {
+    let re = Regex::new(r"(?s)<chart>(.*?)</chart>").ok()?;
+    ChartKind::parse(re.captures(text)?.get(1)?.as_str())
+}
+
+/// Detecta si el resultado de una query se puede dibujar
+pub(super) fn detect(result: &Value, requested: Option<ChartKind>) -> Option<Chart> {
+    let rows = result["rows"].as_array()?;
+    if rows.len() < 2 {
+        return None;
+    }
+
+    let first = rows[0].as_object()?;
+    if first.len() != 2 {
+        return None;
+    }
+
+    let is_numeric = |column: &str| rows.iter().all(|row| row[column].is_number());
+    let columns: Vec<&String> = first.keys().collect();
+    let numeric: Vec<&String> = columns.iter().copied().filter(|c| is_numeric(c)).collect();
+
+    let (label, value) = match numeric.len() {
+        1 => {
+            let value = numeric[0];
+            let label = columns.iter().copied().find(|c| *c != value)?;
+            (label, value)
+        }
+        // Dos columnas numéricas: solo si una de ellas es temporal (año, mes...)
+        2 => {
+            let label = columns.iter().copied().find(|c| is_time_column(c))?;
+            let value = columns.iter().copied().find(|c| *c != label)?;
+            (label, value)
+        }
+        _ => return None,
+    };
+
+    let points: Vec<(String, f64)> = rows
+        .iter()
+        .map(|row| {
+            (
+                value_to_string(&row[label]),
+                row[value].as_f64().unwrap_or(0.0),
+            )
+        })
+        .collect();
+
+    let time_series = is_time_column(label) || points.iter().all(|(l, _)| is_date(l));
+    let kind = requested.unwrap_or(if time_series {
+        ChartKind::Line
+    } else {
+        ChartKind::Bar
+    });
+
+    Some(Chart {
+        kind,
+        label: label.clone(),
+        value: value.clone(),
+        points,
+    })
+}
+
+fn is_time_column(name: &str) -> bool {
+    let name = name.to_lowercase();
+    [
+        "date", "year", "month", "day", "fecha", "año", "mes", "dia", "día",
+    ]
+    .iter()
+    .any(|t| name.contains(t))
+}
+
+fn is_date(text: &str) -> bool {
+    Regex::new(r"^\d{4}(-\d{2}){0,2}")
+        .map(|re| re.is_match(text))
+        .unwrap_or(false)
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max - 1).collect();
+        format!("{}…", cut)
+    }
+}
+
+impl Chart {
+    pub fn with_kind(&self, kind: ChartKind) -> Self {
+        Self {
+            kind,
+            ..self.clone()
+        }
+    }
+
+    fn range(&self) -> (f64, f64) {
+        let min = self
+            .points
+            .iter()
+            .map(|p| p.1)
+            .fold(f64::INFINITY, f64::min);
+        let max = self
+            .points
+            .iter()
+            .map(|p| p.1)
+            .fold(f64::NEG_INFINITY, f64::max);
+        (min.min(0.0), max.max(0.0))
+    }
+
+    fn title(&self) -> String {
+        t!("chart.title", value = self.value, label = self.label)
+    }
+
+    /// Gráfico para la terminal
+    pub fn render_text(&self) -> String {
+        match self.kind {
+            ChartKind::Bar => self.render_bars(),
+            ChartKind::Line => self.render_line(),
+        }
+    }
+
+    fn render_bars(&self) -> String {
+        let (_, max) = self.range();
+        let label_width = self
+            .points
+            .iter()
+            .take(MAX_BARS)
+            .map(|(l, _)| l.chars().count().min(20))
+            .max()
+            .unwrap_or(0);
+
+        let mut output = format!("{}\n", self.title());
+        for (label, value) in self.points.iter().take(MAX_BARS) {
+            let width = if max > 0.0 {
+                ((value.max(0.0) / max) * BAR_WIDTH as f64).round() as usize
+            } else {
+                0
+            };
+            output.push_str(&format!(
+                "{:>label_width$} │{} {}\n",
+                truncate(label, 20),
+                "█".repeat(width),
+                format_value(*value),
+            ));
+        }
+        if self.points.len() > MAX_BARS {
+            output.push_str(&format!(
+                "{}\n",
+                t!("chart.more", count = self.points.len() - MAX_BARS)
+            ));
+        }
+        output
+    }
+
+    fn render_line(&self) -> String {
+        let (min, max) = self.range();
+        let span = if max > min { max - min } else { 1.0 };
+        let n = self.points.len();
+
+        // Series cortas: puntos repartidos a lo ancho; largas: una muestra por columna
+        let (columns, samples): (usize, Vec<(usize, f64)>) = if n >= PLOT_WIDTH {
+            let samples = (0..PLOT_WIDTH)
+                .map(|c| (c, self.points[c * n / PLOT_WIDTH].1))
+                .collect();
+            (PLOT_WIDTH, samples)
+        } else {
+            let spacing = (PLOT_WIDTH - 1) / (n - 1).max(1);
+            let samples = self
+                .points
+                .iter()
+                .enumerate()
+                .map(|(i, (_, value))| (i * spacing, *value))
+                .collect();
+            ((n - 1) * spacing + 1, samples)
+        };
+
+        let mut grid = vec![vec![' '; columns]; PLOT_HEIGHT];
+        for (c, value) in samples {
+            let row = (((value - min) / span) * (PLOT_HEIGHT - 1) as f64).round() as usize;
+            grid[PLOT_HEIGHT - 1 - row][c] = '●';
+        }
+
+        let axis_width = format_value(max).len().max(format_value(min).len());
+        let mut output = format!("{}\n", self.title());
+        for (i, line) in grid.iter().enumerate() {
+            let tick = match i {
+                0 => format_value(max),
+                i if i == PLOT_HEIGHT - 1 => format_value(min),
+                _ => String::new(),
+            };
+            output.push_str(&format!(
+                "{:>axis_width$} │{}\n",
+                tick,
+                line.iter().collect::<String>()
+            ));
+        }
+        output.push_str(&format!("{:>axis_width$} └{}\n", "", "─".repeat(columns)));
+
+        let first = &self.points[0].0;
+        let last = &self.points[self.points.len() - 1].0;
+        let gap = columns.saturating_sub(first.chars().count() + last.chars().count());
+        output.push_str(&format!(
+            "{:>axis_width$}  {}{}{}\n",
+            "",
+            first,
+            " ".repeat(gap.max(1)),
+            last
+        ));
+        output
+    }
+
+    /// Documento SVG independiente
+    pub fn render_svg(&self) -> String {
+        let (min, max) = self.range();
+        let span = if max > min { max - min } else { 1.0 };
+        let plot_width = SVG_WIDTH - 2.0 * SVG_MARGIN;
+        let plot_height = SVG_HEIGHT - 2.0 * SVG_MARGIN;
+        let n = self.points.len() as f64;
+        let y = |value: f64| SVG_MARGIN + plot_height - (value - min) / span * plot_height;
+        let baseline = y(0.0_f64.clamp(min, max));
+
+        let mut body = String::new();
+        match self.kind {
+            ChartKind::Bar => {
+                let slot = plot_width / n;
+                for (i, (label, value)) in self.points.iter().enumerate() {
+                    let x = SVG_MARGIN + i as f64 * slot;
+                    let top = y(*value).min(baseline);
+                    let height = (y(*value) - baseline).abs();
+                    body.push_str(&format!(
+                        "  <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#4f81bd\"><title>{}: {}</title></rect>\n",
+                        x + slot * 0.1,
+                        top,
+                        slot * 0.8,
+                        height,
+                        escape_markup(label),
+                        format_value(*value)
+                    ));
+                }
+            }
+            ChartKind::Line => {
+                let step = if n > 1.0 { plot_width / (n - 1.0) } else { 0.0 };
+                let points: Vec<String> = self
+                    .points
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (_, value))| {
+                        format!("{:.1},{:.1}", SVG_MARGIN + i as f64 * step, y(*value))
+                    })
+                    .collect();
+                body.push_str(&format!(
+                    "  <polyline points=\"{}\" fill=\"none\" stroke=\"#4f81bd\" stroke-width=\"2\"/>\n",
+                    points.join(" ")
+                ));
+                for (i, (label, value)) in self.points.iter().enumerate() {
+                    body.push_str(&format!(
+                        "  <circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"#4f81bd\"><title>{}: {}</title></circle>\n",
+                        SVG_MARGIN + i as f64 * step,
+                        y(*value),
+                        escape_markup(label),
+                        format_value(*value)
+                    ));
+                }
+            }
+        }
+
+        let first = escape_markup(&self.points[0].0);
+        let last = escape_markup(&self.points[self.points.len() - 1].0);
+
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">
+  <rect width="100%" height="100%" fill="white"/>
+  <text x="{cx}" y="30" text-anchor="middle" font-size="16">{title}</text>
+  <line x1="{m}" y1="{m}" x2="{m}" y2="{bottom}" stroke="#333"/>
+  <line x1="{m}" y1="{base:.1}" x2="{right}" y2="{base:.1}" stroke="#333"/>
+  <text x="{tick_x}" y="{m}" text-anchor="end">{max}</text>
+  <text x="{tick_x}" y="{bottom}" text-anchor="end">{min}</text>
+  <text x="{m}" y="{labels_y}">{first}</text>
+  <text x="{right}" y="{labels_y}" text-anchor="end">{last}</text>
+{body}</svg>
+"##,
+            w = SVG_WIDTH,
+            h = SVG_HEIGHT,
+            cx = SVG_WIDTH / 2.0,
+            m = SVG_MARGIN,
+            bottom = SVG_HEIGHT - SVG_MARGIN,
+            right = SVG_WIDTH - SVG_MARGIN,
+            base = baseline,
+            tick_x = SVG_MARGIN - 6.0,
+            labels_y = SVG_HEIGHT - SVG_MARGIN + 20.0,
+            title = escape_markup(&self.title()),
+            max = format_value(max),
+            min = format_value(min),
+            first = first,
+            last = last,
+            body = body,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn chart(kind: ChartKind, points: &[(&str, f64)]) -> Chart {
+        Chart {
+            kind,
+            label: "categoria".to_string(),
+            value: "peliculas".to_string(),
+            points: points.iter().map(|(l, v)| (l.to_string(), *v)).collect(),
+        }
+    }
+
+    #[test]
+    fn labels_and_numbers_are_bars() {
+        let result = json!({"rows": [
+            {"name": "Action", "total": 64},
+            {"name": "Comedy", "total": 58},
+        ]});
+        let chart = detect(&result, None).unwrap();
+        assert_eq!(chart.kind, ChartKind::Bar);
+        assert_eq!(chart.label, "name");
+        assert_eq!(chart.value, "total");
+        assert_eq!(
+            chart.points,
+            vec![("Action".to_string(), 64.0), ("Comedy".to_string(), 58.0)]
+        );
+
+        assert_eq!(
+            detect(&result, Some(ChartKind::Line)).unwrap().kind,
+            ChartKind::Line
+        );
+    }
+
+    #[test]
+    fn time_series_are_lines() {
+        let by_date = json!({"rows": [
+            {"dia": "2005-05-24", "alquileres": 8},
+            {"dia": "2005-05-25", "alquileres": 137},
+        ]});
+        assert_eq!(detect(&by_date, None).unwrap().kind, ChartKind::Line);
+
+        // Dos columnas numéricas: la temporal es la etiqueta
+        let by_year = json!({"rows": [
+            {"total": 10.5, "year": 2005},
+            {"total": 20.0, "year": 2006},
+        ]});
+        let chart = detect(&by_year, None).unwrap();
+        assert_eq!(chart.kind, ChartKind::Line);
+        assert_eq!(chart.label, "year");
+        assert_eq!(chart.value, "total");
+    }
+
+    #[test]
+    fn other_shapes_are_not_charts() {
+        let one_row = json!({"rows": [{"name": "Action", "total": 64}]});
+        assert!(detect(&one_row, None).is_none());
+
+        let three_columns = json!({"rows": [
+            {"a": "x", "b": 1, "c": 2},
+            {"a": "y", "b": 3, "c": 4},
+        ]});
+        assert!(detect(&three_columns, None).is_none());
+
+        let two_numbers = json!({"rows": [{"a": 1, "b": 2}, {"a": 3, "b": 4}]});
+        assert!(detect(&two_numbers, None).is_none());
+
+        let two_texts = json!({"rows": [{"a": "x", "b": "y"}, {"a": "z", "b": "w"}]});
+        assert!(detect(&two_texts, None).is_none());
+
+        let mixed = json!({"rows": [{"a": "x", "b": 1}, {"a": "y", "b": "n/a"}]});
+        assert!(detect(&mixed, None).is_none());
+
+        assert!(detect(&json!({"error": "x"}), None).is_none());
+    }
+
+    #[test]
+    fn bars_scale_to_the_maximum() {
+        let text = chart(ChartKind::Bar, &[("Action", 64.0), ("Comedy", 32.0)]).render_text();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[0], "peliculas por categoria");
+        assert_eq!(lines[1], format!("Action │{} 64", "█".repeat(BAR_WIDTH)));
+        assert_eq!(
+            lines[2],
+            format!("Comedy │{} 32", "█".repeat(BAR_WIDTH / 2))
+        );
+    }
+
+    #[test]
+    fn bars_are_limited() {
+        let points: Vec<(String, f64)> = (0..MAX_BARS + 5)
+            .map(|i| (format!("c{}", i), i as f64))
+            .collect();
+        let points: Vec<(&str, f64)> = points.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+        let text = chart(ChartKind::Bar, &points).render_text();
+
+        assert_eq!(text.lines().count(), 1 + MAX_BARS + 1);
+        assert!(text.ends_with("... y 5 más\n"));
+    }
+
+    #[test]
+    fn line_has_a_point_per_value() {
+        let text = chart(
+            ChartKind::Line,
+            &[("2005-05", 10.0), ("2005-06", 30.0), ("2005-07", 20.0)],
+        )
+        .render_text();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // Título, rejilla, eje y etiquetas de los extremos
+        assert_eq!(lines.len(), 1 + PLOT_HEIGHT + 2);
+        assert_eq!(text.matches('●').count(), 3);
+        assert!(lines[1].starts_with("30 │"));
+        assert!(lines[PLOT_HEIGHT].starts_with(" 0 │"));
+        assert!(lines[PLOT_HEIGHT + 2].contains("2005-05"));
+        assert!(lines[PLOT_HEIGHT + 2].ends_with("2005-07"));
+    }
+
+    #[test]
+    fn svg_escapes_labels_and_title() {
+        let mut chart = chart(ChartKind::Bar, &[("<b>R&B</b>", 3.0), ("\"Drama\"", 1.0)]);
+        chart.value = "<script>".to_string();
+
+        for chart in [chart.clone(), chart.with_kind(ChartKind::Line)] {
+            let svg = chart.render_svg();
+            assert!(svg.contains("&lt;b&gt;R&amp;B&lt;/b&gt;: 3"));
+            assert!(svg.contains("&quot;Drama&quot;: 1"));
+            assert!(svg.contains("&lt;script&gt; por categoria"));
+            assert!(!svg.contains("<b>"));
+            assert!(!svg.contains("<script>"));
+        }
+    }
+}
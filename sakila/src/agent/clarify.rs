@@ -0,0 +1,182 @@
+use regex::Regex;
+
+const TAG: &str = "<clarify>";
+
+/// Pregunta de aclaración del modelo:
+/// `<clarify>¿Pregunta?<option>A</option><option>B</option></clarify>`
+#[derive(Debug, Clone)]
+pub(super) struct Clarification {
+    pub question: String,
+    pub options: Vec<String>,
+}
+
+/// Texto que se muestra mientras el modelo escribe: se corta en cuanto
+/// empieza `<clarify>`, porque la pregunta se presenta aparte con sus
+/// opciones. La etiqueta puede llegar partida entre fragmentos
+pub(super) struct StreamFilter {
+    enabled: bool,
+    hidden: bool,
+    pending: String,
+}
+
+impl StreamFilter {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            hidden: false,
+            pending: String::new(),
+        }
+    }
+
+    /// Parte del texto que ya se puede mostrar
+    pub fn push(&mut self, text: &str) -> String {
+        if !self.enabled {
+            return text.to_string();
+        }
+        if self.hidden {
+            return String::new();
+        }
+
+        self.pending.push_str(text);
+        if let Some(pos) = self.pending.find(TAG) {
+            self.hidden = true;
+            let visible = self.pending[..pos].to_string();
+            self.pending.clear();
+            return visible;
+        }
+
+        // Conservar un posible comienzo de la etiqueta
+        let keep = (1..=TAG.len().min(self.pending.len()))
+            .rev()
+            .find(|&k| {
+                let start = self.pending.len() - k;
+                self.pending.is_char_boundary(start) && TAG.starts_with(&self.pending[start..])
+            })
+            .unwrap_or(0);
+        self.pending.drain(..self.pending.len() - keep).collect()
+    }
+
+    /// Lo que quedaba retenido al terminar la generación
+    pub fn finish(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Aclaración pendiente: la pregunta original espera la respuesta del usuario
+#[derive(Debug, Clone)]
+pub(super) struct Pending {
+    pub question: String,
+    pub options: Vec<String>,
+    /// Aclaraciones ya pedidas para esta pregunta
+    pub rounds: usize,
+}
+
+/// Acepta bloques sin cerrar: `</clarify>` u `</option>` pueden faltar si la
+/// generación se cortó
+pub(super) fn extract(text: &str) -> Option<Clarification> {
+    let block = Regex::new(r"(?s)<clarify>(.*?)(?:</clarify>|$)").ok()?;
+    let content = block.captures(text)?.get(1)?.as_str();
+
+    let mut parts = content.split("<option>");
+    let mut question = parts.next().unwrap_or_default().to_string();
+    let mut options = Vec::new();
+    for part in parts {
+        let (option, rest) = part.split_once("</option>").unwrap_or((part, ""));
+        let option = option.trim();
+        if !option.is_empty() {
+            options.push(option.to_string());
+        }
+        question.push_str(rest);
+    }
+
+    let question = question.replace("</option>", "").trim().to_string();
+    if question.is_empty() {
+        return None;
+    }
+
+    Some(Clarification { question, options })
+}
+
+/// La respuesta puede ser el número de una opción o texto libre
+pub(super) fn resolve(answer: &str, options: &[String]) -> String {
+    let answer = answer.trim();
+    match answer.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= options.len() => options[n - 1].clone(),
+        _ => answer.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn question_with_options() {
+        let clarification =
+            extract("<clarify>¿Qué periodo?<option>2005</option><option>2006</option></clarify>")
+                .unwrap();
+        assert_eq!(clarification.question, "¿Qué periodo?");
+        assert_eq!(clarification.options, vec!["2005", "2006"]);
+    }
+
+    #[test]
+    fn unclosed_blocks() {
+        let clarification =
+            extract("<clarify>¿Qué periodo?<option>2005</option><option>2006").unwrap();
+        assert_eq!(clarification.question, "¿Qué periodo?");
+        assert_eq!(clarification.options, vec!["2005", "2006"]);
+
+        let clarification = extract("Antes de seguir: <clarify>¿Qué tienda?").unwrap();
+        assert_eq!(clarification.question, "¿Qué tienda?");
+        assert!(clarification.options.is_empty());
+    }
+
+    #[test]
+    fn malformed_options() {
+        let clarification =
+            extract("<clarify>¿Cuál? <option></option><option> A </option></option></clarify>")
+                .unwrap();
+        assert_eq!(clarification.question, "¿Cuál?");
+        assert_eq!(clarification.options, vec!["A"]);
+    }
+
+    #[test]
+    fn without_question() {
+        assert!(extract("<clarify><option>A</option></clarify>").is_none());
+        assert!(extract("<clarify>   </clarify>").is_none());
+        assert!(extract("<sql>SELECT 1</sql>").is_none());
+    }
+
+    #[test]
+    fn resolve_numbers_and_text() {
+        let options = vec!["2005".to_string(), "2006".to_string()];
+        assert_eq!(resolve(" 2 ", &options), "2006");
+        assert_eq!(resolve("3", &options), "3");
+        assert_eq!(resolve("los dos", &options), "los dos");
+    }
+
+    #[test]
+    fn filter_hides_the_block() {
+        let mut filter = StreamFilter::new(true);
+        let mut shown = String::new();
+        for chunk in ["Necesito saber algo. <cla", "rify>¿Qué", " año?</clarify>"] {
+            shown.push_str(&filter.push(chunk));
+        }
+        shown.push_str(&filter.finish());
+        assert_eq!(shown, "Necesito saber algo. ");
+    }
+
+    #[test]
+    fn filter_keeps_other_tags() {
+        let mut filter = StreamFilter::new(true);
+        let mut shown = String::new();
+        for chunk in ["a <", " b <s", "ql>SELECT 1</sql> <cl"] {
+            shown.push_str(&filter.push(chunk));
+        }
+        shown.push_str(&filter.finish());
+        assert_eq!(shown, "a < b <sql>SELECT 1</sql> <cl");
+
+        let mut filter = StreamFilter::new(false);
+        assert_eq!(filter.push("<clarify>¿Qué?"), "<clarify>¿Qué?");
+    }
+}
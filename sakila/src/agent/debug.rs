@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use serde_json::json;
+
+use crate::{
+    config::DebugConfig,
+    llm::Completion,
+    t,
+    ui::{Frontend, Notice},
+};
+
+/// Inspector de llamadas al modelo: guarda exactamente lo que recibió y
+/// generó en cada iteración, en un directorio por sesión
+pub(super) struct DebugLog {
+    enabled: bool,
+    dir: PathBuf,
+    calls: usize,
+    last: Option<Completion>,
+}
+
+impl DebugLog {
+    pub fn new(cfg: &DebugConfig, session_id: &str) -> Self {
+        Self {
+            enabled: cfg.enabled,
+            dir: PathBuf::from(&cfg.dir).join(session_id),
+            calls: 0,
+            last: None,
+        }
+    }
+
+    /// Escribe `NNN-prompt.txt`, `NNN-output.txt` y `NNN.json` con los
+    /// parámetros, los tokens y el motivo de parada
+    pub fn record(&mut self, completion: &Completion) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.calls += 1;
+
+        let trace = &completion.trace;
+        let metadata = json!({
+            "call": self.calls,
+            "prompt_tokens": completion.usage.prompt_tokens,
+            "generated_tokens": completion.usage.generated_tokens,
+            "ttft_ms": completion.usage.ttft_ms,
+            "tokens_per_sec": completion.usage.tokens_per_sec,
+            "sampling": trace.sampling,
+            "stop_reason": format!("{:?}", completion.stop_reason),
+        });
+
+        std::fs::create_dir_all(&self.dir)?;
+        let prefix = format!("{:03}", self.calls);
+        std::fs::write(
+            self.dir.join(format!("{}-prompt.txt", prefix)),
+            &trace.prompt,
+        )?;
+        std::fs::write(self.dir.join(format!("{}-output.txt", prefix)), &trace.raw)?;
+        std::fs::write(
+            self.dir.join(format!("{}.json", prefix)),
+            serde_json::to_string_pretty(&metadata)?,
+        )?;
+
+        self.last = Some(completion.clone());
+        Ok(())
+    }
+
+    /// `/debug last`: la última llamada al modelo
+    pub fn show_last(&self, ui: &mut dyn Frontend) -> Result<()> {
+        if !self.enabled {
+            return ui.notice(Notice::Warning, t!("debug.disabled"));
+        }
+        let Some(completion) = &self.last else {
+            return ui.notice(Notice::Warning, t!("debug.empty"));
+        };
+
+        let trace = &completion.trace;
+        let sampling = trace.sampling;
+        ui.notice(
+            Notice::Info,
+            &format!(
+                "{}\ntemperature {} · top_p {} · top_k {} · seed {} · max_length {}",
+                t!("debug.call", call = self.calls, dir = self.dir.display()),
+                sampling.temperature,
+                sampling.top_p,
+                sampling.top_k,
+                sampling.seed,
+                sampling.max_length
+            ),
+        )?;
+        ui.block(
+            &t!("debug.prompt", tokens = completion.usage.prompt_tokens),
+            &trace.prompt,
+        )?;
+        ui.block(
+            &t!(
+                "debug.output",
+                tokens = completion.usage.generated_tokens,
+                stop = format!("{:?}", completion.stop_reason)
+            ),
+            &trace.raw,
+        )
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use super::guard::escape;
+use crate::{config::ResultsConfig, t};
+
+/// Los valores de texto no se recortan por debajo de esta longitud
+const MIN_CELL_CHARS: usize = 16;
+
+/// Estadísticas acumuladas de una columna del resultado
+struct ColumnStats {
+    name: String,
+    non_null: usize,
+    nulls: usize,
+    numeric: usize,
+    sum: f64,
+    min_num: Option<f64>,
+    max_num: Option<f64>,
+    min_text: Option<String>,
+    max_text: Option<String>,
+    frequencies: HashMap<String, usize>,
+}
+
+impl ColumnStats {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            non_null: 0,
+            nulls: 0,
+            numeric: 0,
+            sum: 0.0,
+            min_num: None,
+            max_num: None,
+            min_text: None,
+            max_text: None,
+            frequencies: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.nulls += 1,
+            Value::Number(n) => {
+                self.non_null += 1;
+                if let Some(v) = n.as_f64() {
+                    self.numeric += 1;
+                    self.sum += v;
+                    self.min_num = Some(self.min_num.map_or(v, |m| m.min(v)));
+                    self.max_num = Some(self.max_num.map_or(v, |m| m.max(v)));
+                }
+                *self.frequencies.entry(n.to_string()).or_insert(0) += 1;
+            }
+            other => {
+                self.non_null += 1;
+                let text = value_to_string(other);
+                if self.min_text.as_ref().is_none_or(|m| &text < m) {
+                    self.min_text = Some(text.clone());
+                }
+                if self.max_text.as_ref().is_none_or(|m| &text > m) {
+                    self.max_text = Some(text.clone());
+                }
+                *self.frequencies.entry(text).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        self.numeric > 0 && self.numeric == self.non_null
+    }
+
+    fn render(&self, top_values: usize) -> String {
+        let mut line = t!(
+            "results.column",
+            name = self.name,
+            values = self.non_null,
+            nulls = self.nulls
+        );
+
+        if self.is_numeric() {
+            let mean = self.sum / self.numeric as f64;
+            line.push_str(&t!(
+                "results.numeric",
+                min = format_number(self.min_num.unwrap_or_default()),
+                max = format_number(self.max_num.unwrap_or_default()),
+                mean = format_number(mean)
+            ));
+        } else if let (Some(min), Some(max)) = (&self.min_text, &self.max_text) {
+            line.push_str(&t!("results.text_range", min = min, max = max));
+        }
+
+        line.push_str(&t!("results.distinct", count = self.frequencies.len()));
+
+        // Los valores más frecuentes solo aportan si hay repeticiones
+        if top_values > 0 && self.frequencies.len() < self.non_null {
+            let mut top: Vec<(&String, &usize)> = self.frequencies.iter().collect();
+            top.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+            let top: Vec<String> = top
+                .iter()
+                .take(top_values)
+                .map(|(v, n)| format!("{} ({})", v, n))
+                .collect();
+            line.push_str(&t!("results.top", values = top.join(", ")));
+        }
+
+        line
+    }
+}
+
+pub(super) fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+pub(super) fn format_row(index: usize, row: &Value) -> String {
+    let mut output = format!("{}. ", index);
+    if let Some(obj) = row.as_object() {
+        let values: Vec<String> = obj
+            .iter()
+            .map(|(k, v)| format!("{}: {}", k, value_to_string(v)))
+            .collect();
+        output.push_str(&values.join(", "));
+    }
+    output.push('\n');
+    output
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn collect_stats(rows: &[Value]) -> Vec<ColumnStats> {
+    let columns: Vec<String> = rows
+        .first()
+        .and_then(Value::as_object)
+        .map(|obj| obj.keys().cloned().collect())
+        .unwrap_or_default();
+
+    let mut stats: Vec<ColumnStats> = columns.iter().map(|c| ColumnStats::new(c)).collect();
+    let empty = Map::new();
+
+    for row in rows {
+        let obj = row.as_object().unwrap_or(&empty);
+        for column in stats.iter_mut() {
+            column.add(obj.get(&column.name).unwrap_or(&Value::Null));
+        }
+    }
+
+    stats
+}
+
+fn render(rows: &[Value], stats: &[ColumnStats], edge_rows: usize, top_values: usize) -> String {
+    let count = rows.len();
+    let mut output = format!("{}\n", t!("results.summary", count = count));
+
+    output.push_str(&format!("{}\n", t!("results.columns")));
+    for column in stats {
+        output.push_str(&column.render(top_values));
+        output.push('\n');
+    }
+
+    if edge_rows > 0 {
+        output.push_str(&format!(
+            "{}\n",
+            t!("results.first_rows", count = edge_rows.min(count))
+        ));
+        for (i, row) in rows.iter().take(edge_rows).enumerate() {
+            output.push_str(&format_row(i + 1, row));
+        }
+
+        let tail_start = count.saturating_sub(edge_rows).max(edge_rows);
+        if tail_start < count {
+            if tail_start > edge_rows {
+                output.push_str(&format!(
+                    "{}\n",
+                    t!("results.skipped", count = tail_start - edge_rows)
+                ));
+            }
+            output.push_str(&format!(
+                "{}\n",
+                t!("results.last_rows", count = count - tail_start)
+            ));
+            for (i, row) in rows.iter().enumerate().skip(tail_start) {
+                output.push_str(&format_row(i + 1, row));
+            }
+        }
+    }
+
+    output
+}
+
+/// Resume un resultado grande para el contexto del modelo: estadísticas por
+/// columna, primeras y últimas filas. Reduce el detalle hasta caber en el
+/// presupuesto de tokens configurado.
+pub(super) fn summarize(
+    rows: &[Value],
+    cfg: &ResultsConfig,
+    count_tokens: &dyn Fn(&str) -> usize,
+) -> String {
+    let stats = collect_stats(rows);
+
+    let mut edge_rows = cfg.edge_rows;
+    let mut top_values = cfg.top_values;
+
+    loop {
+        let output = render(rows, &stats, edge_rows, top_values);
+        if count_tokens(&output) <= cfg.token_budget || (edge_rows == 0 && top_values == 0) {
+            return output;
+        }
+
+        // Primero se sacrifican filas de ejemplo, luego los valores frecuentes
+        if edge_rows > 0 {
+            edge_rows /= 2;
+        } else {
+            top_values = 0;
+        }
+    }
+}
+
+/// Ajusta al presupuesto de tokens el texto que genera `render`: primero
+/// recorta los valores de texto largos y, si no basta, quita columnas por la
+/// derecha (dejando al menos una)
+pub(super) fn fit(
+    rows: &[Value],
+    budget: usize,
+    count_tokens: &dyn Fn(&str) -> usize,
+    render: &dyn Fn(&[Value]) -> String,
+) -> String {
+    let mut output = render(rows);
+    let columns: Vec<String> = rows
+        .first()
+        .and_then(Value::as_object)
+        .map(|obj| obj.keys().cloned().collect())
+        .unwrap_or_default();
+    let mut kept = columns.len();
+    let mut max_chars = rows
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|obj| obj.values())
+        .filter_map(Value::as_str)
+        .map(|text| text.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    while count_tokens(&output) > budget {
+        if max_chars > MIN_CELL_CHARS {
+            max_chars = (max_chars / 2).max(MIN_CELL_CHARS);
+        } else if kept > 1 {
+            kept -= 1;
+        } else {
+            break;
+        }
+
+        output = render(&trim(rows, &columns[..kept], max_chars));
+        if kept < columns.len() {
+            output.push_str(&format!(
+                "{}\n",
+                t!(
+                    "results.omitted_columns",
+                    columns = columns[kept..].join(", ")
+                )
+            ));
+        }
+    }
+
+    output
+}
+
+/// Filas con solo `columns` y los textos cortados a `max_chars` caracteres
+fn trim(rows: &[Value], columns: &[String], max_chars: usize) -> Vec<Value> {
+    rows.iter()
+        .map(|row| {
+            let obj: Map<String, Value> = columns
+                .iter()
+                .map(|column| {
+                    let value = match &row[column] {
+                        Value::String(text) if text.chars().count() > max_chars => {
+                            let mut text: String = text.chars().take(max_chars).collect();
+                            text.push('…');
+                            Value::String(text)
+                        }
+                        value => value.clone(),
+                    };
+                    (column.clone(), value)
+                })
+                .collect();
+            Value::Object(obj)
+        })
+        .collect()
+}
+
+/// Resumen de un turno que se conserva en memoria: pregunta, SQL final
+/// y las primeras filas del resultado
+pub(super) fn turn_digest(
+    question: &str,
+    sql: &str,
+    result: Option<&Value>,
+    rows: usize,
+) -> String {
+    let mut digest = format!(
+        "<turn_digest>\n{}\nSQL: {}\n",
+        t!("results.digest_question", question = question),
+        sql
+    );
+
+    if let Some(result) = result {
+        let all_rows = result["rows"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        digest.push_str(&format!(
+            "{}\n",
+            t!("results.digest_result", count = all_rows.len())
+        ));
+        for (i, row) in all_rows.iter().take(rows).enumerate() {
+            digest.push_str(&escape(&format_row(i + 1, row)));
+        }
+        if all_rows.len() > rows {
+            digest.push_str("...\n");
+        }
+    }
+
+    digest.push_str("</turn_digest>");
+    digest
+}
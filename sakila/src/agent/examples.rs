@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::ExamplesConfig, t};
+
+// Parámetros estándar de BM25
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Par (pregunta, SQL) marcado como correcto por el usuario
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct Example {
+    pub question: String,
+    pub sql: String,
+}
+
+/// Almacén local de ejemplos con un índice BM25 sobre las preguntas
+pub(super) struct ExampleStore {
+    path: PathBuf,
+    top_k: usize,
+    examples: Vec<Example>,
+    documents: Vec<Vec<String>>,
+}
+
+impl ExampleStore {
+    pub fn load(cfg: &ExamplesConfig) -> Result<Self> {
+        let path = PathBuf::from(&cfg.file);
+        let mut store = Self {
+            path,
+            top_k: cfg.top_k,
+            examples: Vec::new(),
+            documents: Vec::new(),
+        };
+
+        if store.path.exists() {
+            let content = std::fs::read_to_string(&store.path)?;
+            for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                match serde_json::from_str::<Example>(line) {
+                    Ok(example) => store.index(example),
+                    Err(err) => tracing::warn!("⚠️  Ejemplo inválido en {:?}: {}", store.path, err),
+                }
+            }
+            tracing::info!("📚 {} ejemplos cargados", store.examples.len());
+        }
+
+        Ok(store)
+    }
+
+    /// Guarda un ejemplo en disco. Devuelve `false` si ya existía.
+    pub fn add(&mut self, example: Example) -> Result<bool> {
+        if self
+            .examples
+            .iter()
+            .any(|e| e.question == example.question && e.sql == example.sql)
+        {
+            return Ok(false);
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        append_line(&self.path, &serde_json::to_string(&example)?)?;
+        self.index(example);
+
+        Ok(true)
+    }
+
+    /// Ejemplos más parecidos a la pregunta, de mayor a menor puntuación
+    pub fn search(&self, question: &str) -> Vec<&Example> {
+        let query = tokenize(question);
+        if query.is_empty() || self.documents.is_empty() {
+            return Vec::new();
+        }
+
+        let n = self.documents.len() as f64;
+        let avg_len = self.documents.iter().map(Vec::len).sum::<usize>() as f64 / n;
+
+        let mut scores: Vec<(usize, f64)> = self
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(i, doc)| {
+                let score = query
+                    .iter()
+                    .map(|term| {
+                        let tf = doc.iter().filter(|t| *t == term).count() as f64;
+                        if tf == 0.0 {
+                            return 0.0;
+                        }
+                        let df = self.documents.iter().filter(|d| d.contains(term)).count() as f64;
+                        let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        let norm = K1 * (1.0 - B + B * doc.len() as f64 / avg_len);
+                        idf * tf * (K1 + 1.0) / (tf + norm)
+                    })
+                    .sum::<f64>();
+                (i, score)
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scores
+            .iter()
+            .take(self.top_k)
+            .map(|(i, _)| &self.examples[*i])
+            .collect()
+    }
+
+    fn index(&mut self, example: Example) {
+        self.documents.push(tokenize(&example.question));
+        self.examples.push(example);
+    }
+}
+
+/// Mensaje de sistema con ejemplos few-shot para la pregunta actual
+pub(super) fn render(examples: &[&Example]) -> String {
+    let mut output = format!("<examples>\n{}\n", t!("prompt.examples"));
+    for example in examples {
+        output.push_str(&format!(
+            "\nUser: {}\nAssistant: <sql>{}</sql>\n",
+            example.question, example.sql
+        ));
+    }
+    output.push_str("</examples>");
+    output
+}
+
+pub(super) fn append_line(path: &Path, line: &str) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Minúsculas, sin acentos y sin palabras muy cortas
+fn tokenize(text: &str) -> Vec<String> {
+    normalize(text)
+        .into_iter()
+        .filter(|word| word.chars().count() > 2)
+        .collect()
+}
+
+/// Palabras en minúsculas y sin acentos
+pub(super) fn normalize(text: &str) -> Vec<String> {
+    let accents: HashMap<char, char> = [
+        ('á', 'a'),
+        ('é', 'e'),
+        ('í', 'i'),
+        ('ó', 'o'),
+        ('ú', 'u'),
+        ('ü', 'u'),
+        ('ñ', 'n'),
+    ]
+    .into_iter()
+    .collect();
+
+    text.to_lowercase()
+        .chars()
+        .map(|c| *accents.get(&c).unwrap_or(&c))
+        .collect::<String>()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(questions: &[&str], top_k: usize) -> ExampleStore {
+        let mut store = ExampleStore {
+            path: PathBuf::new(),
+            top_k,
+            examples: Vec::new(),
+            documents: Vec::new(),
+        };
+        for (i, question) in questions.iter().enumerate() {
+            store.index(Example {
+                question: question.to_string(),
+                sql: format!("SELECT {}", i),
+            });
+        }
+        store
+    }
+
+    fn questions<'a>(results: &[&'a Example]) -> Vec<&'a str> {
+        results.iter().map(|e| e.question.as_str()).collect()
+    }
+
+    #[test]
+    fn tokenize_strips_accents_and_short_words() {
+        assert_eq!(
+            tokenize("¿Cuántas películas hay en la categoría Acción?"),
+            vec!["cuantas", "peliculas", "hay", "categoria", "accion"]
+        );
+    }
+
+    #[test]
+    fn most_relevant_example_first() {
+        let store = store(
+            &[
+                "¿Cuántos clientes hay por país?",
+                "¿Qué películas de acción alquilaron los clientes de Canadá?",
+                "¿Cuántas películas hay por categoría?",
+            ],
+            3,
+        );
+
+        let results = store.search("películas de cada categoría");
+        assert_eq!(results[0].question, "¿Cuántas películas hay por categoría?");
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn rare_terms_weigh_more() {
+        let store = store(
+            &[
+                "total de pagos por tienda",
+                "total de alquileres por tienda",
+                "total de pagos por cliente",
+            ],
+            3,
+        );
+
+        // "cliente" aparece en un solo documento, "tienda" en dos
+        let results = store.search("pagos cliente tienda");
+        assert_eq!(results[0].question, "total de pagos por cliente");
+    }
+
+    #[test]
+    fn top_k_and_no_matches() {
+        let store = store(&["actores", "actores y películas", "actores famosos"], 2);
+        assert_eq!(store.search("actores").len(), 2);
+        assert!(store.search("inventario").is_empty());
+        assert!(store.search("de la").is_empty());
+
+        let results = store.search("PELICULAS");
+        assert_eq!(questions(&results), vec!["actores y películas"]);
+    }
+}
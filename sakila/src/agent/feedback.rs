@@ -0,0 +1,294 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::{TOOL_MESSAGES, examples::append_line};
+use crate::{chat::Message, config::FeedbackConfig, llm::template::ChatTemplate};
+
+/// Valoración de un turno del agente
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Label {
+    Good,
+    Bad,
+    Fix,
+}
+
+/// Último turno completado, antes de limpiar los mensajes de herramientas
+#[derive(Debug, Clone)]
+pub(super) struct TurnRecord {
+    pub question: String,
+    pub sql: Option<String>,
+    pub messages: Vec<Message>,
+}
+
+/// Valoración guardada junto con la conversación completa
+#[derive(Debug, Serialize, Deserialize)]
+struct Feedback {
+    timestamp: u64,
+    label: Label,
+    question: String,
+    sql: Option<String>,
+    fix: Option<String>,
+    messages: Vec<Message>,
+    rendered: String,
+}
+
+pub(super) struct FeedbackStore {
+    path: PathBuf,
+}
+
+impl FeedbackStore {
+    pub fn new(cfg: &FeedbackConfig) -> Self {
+        Self {
+            path: PathBuf::from(&cfg.file),
+        }
+    }
+
+    pub fn record(
+        &self,
+        template: &ChatTemplate,
+        turn: &TurnRecord,
+        label: Label,
+        fix: Option<String>,
+    ) -> Result<()> {
+        let feedback = Feedback {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            label,
+            question: turn.question.clone(),
+            sql: turn.sql.clone(),
+            fix,
+            messages: turn.messages.clone(),
+            rendered: template.render_conversation(&turn.messages)?,
+        };
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        append_line(&self.path, &serde_json::to_string(&feedback)?)
+    }
+
+    /// Exporta las valoraciones como JSONL en formato chat para fine-tuning.
+    /// Devuelve el número de ejemplos escritos.
+    pub fn export(&self, template: &ChatTemplate, output: &str) -> Result<usize> {
+        if !self.path.exists() {
+            return Ok(0);
+        }
+
+        let content = std::fs::read_to_string(&self.path)?;
+        let mut writer = BufWriter::new(File::create(output)?);
+        let mut exported = 0;
+
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let feedback: Feedback = match serde_json::from_str(line) {
+                Ok(feedback) => feedback,
+                Err(err) => {
+                    tracing::warn!("⚠️  Valoración inválida en {:?}: {}", self.path, err);
+                    continue;
+                }
+            };
+
+            let record = match (feedback.label, &feedback.fix) {
+                (Label::Fix, Some(fix)) => fix_record(template, &feedback, fix)?,
+                _ => json!({
+                    "label": feedback.label,
+                    "messages": to_chat(&feedback.messages),
+                    "text": feedback.rendered,
+                }),
+            };
+
+            writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+            exported += 1;
+        }
+
+        writer.flush()?;
+        Ok(exported)
+    }
+}
+
+/// Par de preferencia: la conversación hasta la última query del modelo,
+/// con la query corregida como respuesta elegida y la original como rechazada
+fn fix_record(template: &ChatTemplate, feedback: &Feedback, fix: &str) -> Result<Value> {
+    let last_sql = feedback.messages.iter().rposition(|m| match m {
+        Message::Assistant { content } => content.contains("<sql>"),
+        _ => false,
+    });
+    let prompt_len = last_sql.unwrap_or(feedback.messages.len());
+    let prompt = &feedback.messages[..prompt_len];
+
+    let chosen = Message::Assistant {
+        content: format!("<sql>{}</sql>", fix),
+    };
+    let rejected = last_sql.map(|i| feedback.messages[i].clone());
+
+    let mut messages = prompt.to_vec();
+    messages.push(chosen.clone());
+
+    Ok(json!({
+        "label": feedback.label,
+        "messages": to_chat(&messages),
+        "text": template.render_conversation(&messages)?,
+        "chosen": to_chat(&[chosen]),
+        "rejected": to_chat(&rejected.into_iter().collect::<Vec<_>>()),
+    }))
+}
+
+/// Mensajes con roles del chat template de Qwen3: los resultados de las
+/// queries son respuestas de herramienta (`tool`)
+fn to_chat(messages: &[Message]) -> Vec<Value> {
+    messages
+        .iter()
+        .map(|message| match message {
+            Message::System { content }
+                if TOOL_MESSAGES.iter().any(|tag| content.starts_with(tag)) =>
+            {
+                json!({"role": "tool", "content": content})
+            }
+            Message::System { content } => json!({"role": "system", "content": content}),
+            Message::User { content } => json!({"role": "user", "content": content}),
+            Message::Assistant { content } => json!({"role": "assistant", "content": content}),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ThinkingConfig, TokenizerConfig};
+
+    fn template() -> ChatTemplate {
+        let config = TokenizerConfig {
+            repo: String::new(),
+            file: String::new(),
+            eos_token: String::new(),
+            system_template: "[system]{{ message }}\n".to_string(),
+            user_template: "[user]{{ message }}\n".to_string(),
+            assistant_template: "[assistant]{{ message }}\n".to_string(),
+            start_completion: "[assistant]".to_string(),
+            thinking_start_completion: "[assistant]".to_string(),
+            think_start: "<think>".to_string(),
+            think_end: "</think>".to_string(),
+            banned_tokens: Vec::new(),
+            stop_tokens: Vec::new(),
+        };
+        let thinking = ThinkingConfig {
+            enabled: false,
+            budget: 0,
+        };
+        ChatTemplate::new(&config, &thinking).unwrap()
+    }
+
+    fn system(content: &str) -> Message {
+        Message::System {
+            content: content.to_string(),
+        }
+    }
+
+    fn user(content: &str) -> Message {
+        Message::User {
+            content: content.to_string(),
+        }
+    }
+
+    fn assistant(content: &str) -> Message {
+        Message::Assistant {
+            content: content.to_string(),
+        }
+    }
+
+    fn roles(chat: &[Value]) -> Vec<&str> {
+        chat.iter().map(|m| m["role"].as_str().unwrap()).collect()
+    }
+
+    fn feedback(messages: Vec<Message>) -> Feedback {
+        Feedback {
+            timestamp: 0,
+            label: Label::Fix,
+            question: "¿Cuántos actores hay?".to_string(),
+            sql: None,
+            fix: None,
+            messages,
+            rendered: String::new(),
+        }
+    }
+
+    #[test]
+    fn results_and_errors_are_tool_messages() {
+        let chat = to_chat(&[
+            system("Eres un asistente"),
+            user("¿Cuántos actores hay?"),
+            assistant("<sql>SELECT COUNT(*) FROM actors</sql>"),
+            system("<sql_error>no such table: actors</sql_error>"),
+            assistant("<sql>SELECT COUNT(*) FROM actor</sql>"),
+            system("<sql_result>200</sql_result>"),
+            system("<turn_digest>resumen</turn_digest>"),
+            assistant("Hay 200 actores"),
+        ]);
+        assert_eq!(
+            roles(&chat),
+            vec![
+                "system",
+                "user",
+                "assistant",
+                "tool",
+                "assistant",
+                "tool",
+                "system",
+                "assistant"
+            ]
+        );
+        assert_eq!(chat[5]["content"], "<sql_result>200</sql_result>");
+    }
+
+    #[test]
+    fn fix_replaces_the_last_query() {
+        let feedback = feedback(vec![
+            system("Eres un asistente"),
+            user("¿Cuántos actores hay?"),
+            assistant("<sql>SELECT COUNT(*) FROM actors</sql>"),
+            system("<sql_error>no such table: actors</sql_error>"),
+            assistant("<sql>SELECT COUNT(*) FROM film</sql>"),
+            system("<sql_result>1000</sql_result>"),
+            assistant("Hay 1000 actores"),
+        ]);
+        let record = fix_record(&template(), &feedback, "SELECT COUNT(*) FROM actor").unwrap();
+
+        let chosen =
+            vec![json!({"role": "assistant", "content": "<sql>SELECT COUNT(*) FROM actor</sql>"})];
+        let rejected =
+            vec![json!({"role": "assistant", "content": "<sql>SELECT COUNT(*) FROM film</sql>"})];
+        assert_eq!(record["chosen"], json!(chosen));
+        assert_eq!(record["rejected"], json!(rejected));
+
+        // El prompt llega hasta la query rechazada, sin incluirla
+        let messages = record["messages"].as_array().unwrap();
+        assert_eq!(
+            roles(messages),
+            vec!["system", "user", "assistant", "tool", "assistant"]
+        );
+        assert_eq!(messages.last(), chosen.first());
+        assert!(
+            record["text"]
+                .as_str()
+                .unwrap()
+                .ends_with("[assistant]<sql>SELECT COUNT(*) FROM actor</sql>\n")
+        );
+    }
+
+    #[test]
+    fn fix_without_a_query_has_nothing_rejected() {
+        let feedback = feedback(vec![user("Hola"), assistant("¡Hola!")]);
+        let record = fix_record(&template(), &feedback, "SELECT 1").unwrap();
+
+        assert_eq!(record["rejected"], json!([]));
+        let messages = record["messages"].as_array().unwrap();
+        assert_eq!(roles(messages), vec!["user", "assistant", "assistant"]);
+    }
+}
@@ -1,131 +1,686 @@
-use color_eyre::Result;
-use sqlx::{Pool, Sqlite};
-use std::io::{Write, stdin, stdout};
+use color_eyre::{Result, eyre::eyre};
+use serde_json::Value;
+use std::{
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
 
-use colored::Colorize;
+use futures_util::StreamExt;
+use tracing::{Instrument, field::Empty};
 
 use crate::{
+    agent::{
+        audit::{AuditEvent, AuditLog},
+        cache::ResultCache,
+        chart::{Chart, ChartKind},
+        clarify::{Clarification, Pending, StreamFilter},
+        debug::DebugLog,
+        examples::{Example, ExampleStore},
+        feedback::{FeedbackStore, Label, TurnRecord},
+        guard::{Finding, Guard},
+        pipeline::{Pipeline, Writer},
+        plan::{PlanAnalyzer, PlanReport},
+        report::ReportEntry,
+        router::{Route, RouteMethod},
+        verify::Observed,
+    },
+    cancel::CancelToken,
     chat::Message,
-    config::AgentConfig,
-    llm::{Llm, stream::PrintCallback},
+    config::{
+        AgentConfig, ClarifyConfig, DigestConfig, ResultsConfig, RouterConfig, VerificationConfig,
+        VerificationMode,
+    },
+    db::Database,
+    llm::{
+        StopReason,
+        worker::{Event, LlmWorker},
+    },
+    t,
+    ui::{Frontend, Notice, Session},
 };
 
 pub struct Agent {
-    llm: Llm,
-    db: Pool<Sqlite>,
+    llm: LlmWorker,
+    db: Database,
     memory: Vec<Message>,
     max_iterations: usize,
     last_sql: Option<String>,
+    results: ResultsConfig,
+    verification: VerificationConfig,
+    turn_results: Vec<Value>,
+    /// Resultados del turno tal como los vio el modelo, para la verificación
+    turn_observed: Vec<Observed>,
+    turn_sql: Option<String>,
+    examples: ExampleStore,
+    feedback: FeedbackStore,
+    last_turn: Option<TurnRecord>,
+    last_chart: Option<Chart>,
+    cancel: CancelToken,
+    audit: AuditLog,
+    turn_question: String,
+    router: RouterConfig,
+    plan: PlanAnalyzer,
+    digests: DigestConfig,
+    cache: ResultCache,
+    debug: DebugLog,
+    database: String,
+    /// Turnos de la sesión para /report
+    history: Vec<ReportEntry>,
+    /// Última query ejecutada, para /rerun
+    last_query: Option<String>,
+    clarify: ClarifyConfig,
+    /// Pregunta a la espera de que el usuario responda una aclaración
+    pending: Option<Pending>,
+    clarify_rounds: usize,
+    pipeline: Pipeline,
+    guard: Guard,
+    ui: Box<dyn Frontend>,
 }
 
+/// Prefijos de los mensajes de sistema con resultados de herramientas
+const TOOL_MESSAGES: [&str; 2] = ["<sql_result>", "<sql_error>"];
+
+/// Prefijo de los resúmenes de turnos anteriores
+const DIGEST_TAG: &str = "<turn_digest>";
+
+/// Prefijos de los mensajes de sistema que solo viven durante un turno
+const TURN_MESSAGES: [&str; 9] = [
+    "<sql_result>",
+    "<sql_error>",
+    "<verification>",
+    "<examples>",
+    "<route>",
+    "<schema>",
+    "<previous_query>",
+    "<plan_hint>",
+    "<clarify_limit>",
+];
+
+mod audit;
+mod cache;
+mod chart;
+mod clarify;
+mod debug;
+mod digest;
+mod examples;
+mod feedback;
+mod guard;
+mod pipeline;
+mod plan;
+mod report;
+mod router;
 mod sql;
+mod verify;
 
 impl Agent {
-    pub fn new(cfg: &AgentConfig, llm: Llm, db: Pool<Sqlite>) -> Self {
+    pub async fn new(
+        cfg: &AgentConfig,
+        llm: LlmWorker,
+        db: Database,
+        database: &str,
+        fingerprint: &str,
+        ui: Box<dyn Frontend>,
+    ) -> Result<Self> {
+        // El system prompt es una plantilla con el esquema de la base de datos
+        let mut context = tera::Context::new();
+        context.insert("database", database);
+        context.insert("schema", &db.describe_schema().await?);
+        context.insert("dialect", db.dialect().name());
+        context.insert("dialect_hints", db.dialect().hints());
+        let mut system_prompt = tera::Tera::one_off(cfg.system_prompt.get(), &context, false)?;
+        let clarify = cfg.clarify.enabled.then(|| cfg.clarify.prompt.get().trim());
+        if let Some(clarify) = clarify {
+            system_prompt = format!("{}\n\n{}", system_prompt.trim_end(), clarify);
+        }
         let system_message = Message::System {
-            content: cfg.system_prompt.clone(),
+            content: system_prompt,
         };
-        Self {
+        let session_id = new_session_id()?;
+        Ok(Self {
             llm,
             db,
             memory: vec![system_message],
             max_iterations: cfg.max_iterations,
             last_sql: None,
-        }
+            results: cfg.results.clone(),
+            verification: cfg.verification.clone(),
+            turn_results: Vec::new(),
+            turn_observed: Vec::new(),
+            turn_sql: None,
+            examples: ExampleStore::load(&cfg.examples)?,
+            feedback: FeedbackStore::new(&cfg.feedback),
+            last_turn: None,
+            last_chart: None,
+            cancel: CancelToken::new(),
+            audit: AuditLog::new(&cfg.audit, &session_id, database),
+            turn_question: String::new(),
+            router: cfg.router.clone(),
+            plan: PlanAnalyzer::new(&cfg.plan),
+            digests: cfg.digests.clone(),
+            cache: ResultCache::load(&cfg.cache, fingerprint)?,
+            debug: DebugLog::new(&cfg.debug, &session_id),
+            database: database.to_string(),
+            history: Vec::new(),
+            last_query: None,
+            clarify: cfg.clarify.clone(),
+            pending: None,
+            clarify_rounds: 0,
+            pipeline: Pipeline::new(&cfg.pipeline, &context, clarify)?,
+            guard: Guard::new(&cfg.injection)?,
+            ui,
+        })
     }
 
     pub async fn run(&mut self) -> Result<()> {
-        println!("{}", "🎬 Sakila Agent".bright_magenta().bold());
-        println!("{}", "Type /exit to quit\n".dimmed());
+        let schema = self.db.describe_schema().await?;
+        self.ui.start(&Session {
+            database: &self.database,
+            schema: &schema,
+            cancel: &self.cancel,
+        })?;
 
         // Chat
-        loop {
-            // User input
-            println!("{}", format!("\n─ {} ─", "You").bright_cyan().bold());
-            stdout().flush()?;
-
-            let mut input = String::new();
-            stdin().read_line(&mut input)?;
+        while let Some(input) = self.ui.read_input()? {
             let input = input.trim();
 
             if input.is_empty() {
                 continue;
             }
+            self.cancel.disarm();
 
             if input == "/exit" {
-                println!("{}", "👋 Adiós!".bright_yellow());
                 break;
             }
 
-            // Add user message to memory
-            let user_message = Message::User {
-                content: input.to_string(),
+            if input.starts_with('/') {
+                self.run_command(input).await?;
+                continue;
+            }
+
+            // Ctrl-C a partir de aquí (también durante el enrutado) cancela la
+            // respuesta, no la sesión
+            self.cancel.start();
+
+            // La respuesta a una aclaración retoma la pregunta original
+            let pending = self.pending.take();
+            let route = match pending {
+                Some(_) => Route::Data,
+                None => self.route(input).await?,
             };
+            let (question, content) = match &pending {
+                Some(pending) => {
+                    let answer = clarify::resolve(input, &pending.options);
+                    (format!("{} ({})", pending.question, answer), answer)
+                }
+                None => (input.to_string(), input.to_string()),
+            };
+            self.clarify_rounds = pending.map_or(0, |pending| pending.rounds);
+            let question = question.as_str();
+
+            // Few-shot dinámico con ejemplos parecidos de sesiones anteriores
+            let similar = match route {
+                Route::Data | Route::FollowUp => self.examples.search(question),
+                Route::Schema | Route::SmallTalk => Vec::new(),
+            };
+            if !similar.is_empty() {
+                let examples_message = Message::System {
+                    content: examples::render(&similar),
+                };
+                self.memory.push(examples_message);
+            }
+
+            // Add user message to memory
+            let user_message = Message::User { content };
             self.memory.push(user_message);
 
-            let mut iterations = 0;
-            while iterations < self.max_iterations && self.run_agent().await? {
-                iterations += 1;
+            self.turn_results.clear();
+            self.turn_observed.clear();
+            self.turn_sql = None;
+            self.turn_question = question.to_string();
+
+            self.follow_route(route).await?;
+
+            if self.cancel.is_cancelled() {
+                self.pending = None;
+                self.ui
+                    .notice(Notice::Warning, &format!("\n{}", t!("agent.interrupted")))?;
+            }
+            // Mientras se espera una aclaración no hay respuesta que verificar
+            let clarifying = self.pending.is_some();
+            if self.verification.enabled
+                && !self.cancel.is_cancelled()
+                && !clarifying
+                && matches!(route, Route::Data | Route::FollowUp)
+            {
+                self.verify_answer(question).await?;
             }
+            self.cancel.finish();
 
-            if iterations >= self.max_iterations {
-                println!(
-                    "{}",
-                    "\n⚠️  Límite de iteraciones alcanzado".bright_yellow()
-                );
+            if let Some(Message::Assistant { content }) = self.memory.last() {
+                self.audit.log(AuditEvent::Answer {
+                    question,
+                    answer: content,
+                })?;
+
+                if !self.cancel.is_cancelled() && !clarifying {
+                    let ran_sql = self.turn_sql.is_some();
+                    self.history.push(ReportEntry {
+                        question: question.to_string(),
+                        answer: content.clone(),
+                        sql: self.turn_sql.clone(),
+                        result: self.turn_results.last().cloned(),
+                        chart: self.last_chart.clone().filter(|_| ran_sql),
+                    });
+                }
             }
 
+            self.last_turn = Some(TurnRecord {
+                question: question.to_string(),
+                sql: self.turn_sql.clone(),
+                messages: self.memory.clone(),
+            });
+
             // Clean system tools messages
             self.memory.retain(|msg| match msg {
                 Message::System { content } => {
-                    !content.starts_with("<sql_result>") && !content.starts_with("<sql_error>")
+                    !TURN_MESSAGES.iter().any(|tag| content.starts_with(tag))
                 }
                 _ => true,
             });
             self.last_sql = None;
 
-            println!();
+            // Los resultados se sustituyen por un resumen del turno
+            match self.turn_sql.take() {
+                Some(sql) if !self.cancel.is_cancelled() => self.push_digest(question, &sql),
+                _ => {}
+            }
+
+            self.ui.turn_finished()?;
+        }
+
+        self.ui.finish()
+    }
+
+    async fn run_command(&mut self, input: &str) -> Result<()> {
+        let (command, argument) = input.split_once(' ').unwrap_or((input, ""));
+        let argument = argument.trim();
+
+        match command {
+            "/good" => self.rate(Label::Good, None),
+            "/bad" => self.rate(Label::Bad, None),
+            "/fix" if !argument.is_empty() => self.rate(Label::Fix, Some(argument.to_string())),
+            "/chart" => self.chart_command(argument),
+            "/sql" if !argument.is_empty() => self.run_user_sql(argument).await,
+            "/rerun" => match self.last_query.clone() {
+                Some(sql) => self.run_user_sql(&sql).await,
+                None => self.ui.notice(Notice::Warning, t!("agent.no_query")),
+            },
+            "/report" if !argument.is_empty() => {
+                match report::write(Path::new(argument), &self.database, &self.history) {
+                    Ok(()) => self.ui.notice(
+                        Notice::Success,
+                        &t!(
+                            "agent.report_saved",
+                            count = self.history.len(),
+                            file = argument
+                        ),
+                    ),
+                    Err(err) => self.ui.notice(Notice::Warning, &format!("⚠️  {}", err)),
+                }
+            }
+            "/debug" if argument == "last" => self.debug.show_last(self.ui.as_mut()),
+            "/export" if !argument.is_empty() => {
+                match self.feedback.export(self.llm.get_template(), argument) {
+                    Ok(exported) => self.ui.notice(
+                        Notice::Success,
+                        &t!("agent.exported", count = exported, file = argument),
+                    ),
+                    Err(err) => self.ui.notice(Notice::Warning, &format!("⚠️  {}", err)),
+                }
+            }
+            _ => self.ui.notice(
+                Notice::Warning,
+                &t!("agent.unknown_command", command = input),
+            ),
+        }
+    }
+
+    /// `/chart bar|line` vuelve a dibujar el último gráfico y
+    /// `/chart svg <file>` lo guarda como SVG
+    fn chart_command(&mut self, argument: &str) -> Result<()> {
+        let Some(chart) = self.last_chart.as_ref() else {
+            return self.ui.notice(Notice::Warning, t!("agent.no_chart"));
+        };
+
+        let (subcommand, file) = argument.split_once(' ').unwrap_or((argument, ""));
+        match (subcommand, file.trim()) {
+            // Como /report: un error al escribir no termina la sesión
+            ("svg", file) if !file.is_empty() => match std::fs::write(file, chart.render_svg()) {
+                Ok(()) => self
+                    .ui
+                    .notice(Notice::Success, &t!("agent.chart_saved", file = file)),
+                Err(err) => self.ui.notice(Notice::Warning, &format!("⚠️  {}", err)),
+            },
+            (kind, _) => match ChartKind::parse(kind) {
+                Some(kind) => {
+                    let chart = chart.with_kind(kind);
+                    self.ui.chart(&chart.render_text())?;
+                    self.last_chart = Some(chart);
+                    Ok(())
+                }
+                None => self.ui.notice(Notice::Warning, t!("agent.chart_usage")),
+            },
+        }
+    }
+
+    fn rate(&mut self, label: Label, fix: Option<String>) -> Result<()> {
+        let Some(turn) = self.last_turn.as_ref() else {
+            return self.ui.notice(Notice::Warning, t!("agent.nothing_to_rate"));
+        };
+
+        // Un fallo al guardar (disco lleno, directorio de solo lectura) se
+        // avisa sin terminar la sesión
+        if let Err(err) = self
+            .feedback
+            .record(self.llm.get_template(), turn, label, fix.clone())
+        {
+            return self.ui.notice(Notice::Warning, &format!("⚠️  {}", err));
+        }
+        self.ui.notice(Notice::Success, t!("agent.rating_saved"))?;
+
+        // Las queries buenas (o corregidas) alimentan los ejemplos few-shot
+        let sql = match label {
+            Label::Good => turn.sql.clone(),
+            Label::Fix => fix,
+            Label::Bad => None,
+        };
+        if let Some(sql) = sql {
+            let example = Example {
+                question: turn.question.clone(),
+                sql,
+            };
+            match self.examples.add(example) {
+                Ok(true) => self.ui.notice(Notice::Info, t!("agent.example_saved"))?,
+                Ok(false) => {}
+                Err(err) => self.ui.notice(Notice::Warning, &format!("⚠️  {}", err))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Guarda el resumen del turno y descarta los más antiguos
+    fn push_digest(&mut self, question: &str, sql: &str) {
+        let content =
+            digest::turn_digest(question, sql, self.turn_results.last(), self.digests.rows);
+        self.memory.push(Message::System { content });
+
+        let is_digest = |msg: &Message| matches!(msg, Message::System { content } if content.starts_with(DIGEST_TAG));
+        let mut excess = self
+            .memory
+            .iter()
+            .filter(|msg| is_digest(msg))
+            .count()
+            .saturating_sub(self.digests.max_turns);
+        self.memory.retain(|msg| {
+            if excess > 0 && is_digest(msg) {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Decide la ruta del mensaje: primero reglas y, si no deciden, el modelo
+    async fn route(&mut self, question: &str) -> Result<Route> {
+        let has_previous = self.last_turn.is_some();
+        let (route, method) = if !self.router.enabled {
+            (Route::Data, RouteMethod::Default)
+        } else if let Some(route) = router::by_rules(question, has_previous) {
+            (route, RouteMethod::Rules)
+        } else if self.router.classifier {
+            let mut content = question.to_string();
+            if let Some(turn) = &self.last_turn {
+                content = t!(
+                    "prompt.previous_message",
+                    previous = turn.question,
+                    message = question
+                );
+            }
+            let messages = [
+                Message::System {
+                    content: self.router.prompt.get().clone(),
+                },
+                Message::User { content },
+            ];
+            let labels = router::labels().map(String::from);
+            let index = self.llm.classify(&messages, &labels).await?;
+            (router::ROUTES[index], RouteMethod::Model)
+        } else {
+            (Route::Data, RouteMethod::Default)
+        };
+
+        // Sin turno anterior no hay nada que seguir
+        let route = match route {
+            Route::FollowUp if !has_previous => Route::Data,
+            route => route,
+        };
+
+        tracing::info!(?route, ?method, "Ruta del mensaje");
+        self.audit.log(AuditEvent::Route {
+            question,
+            route,
+            method,
+        })?;
+        Ok(route)
+    }
+
+    async fn follow_route(&mut self, route: Route) -> Result<()> {
+        match route {
+            Route::Data => self.run_loop().await,
+            Route::FollowUp => {
+                if let Some(sql) = self.last_turn.as_ref().and_then(|turn| turn.sql.clone()) {
+                    self.memory.push(Message::System {
+                        content: format!("<previous_query>{}</previous_query>", sql),
+                    });
+                }
+                self.run_loop().await
+            }
+            Route::Schema => {
+                let schema = self.db.describe_schema().await?;
+                self.memory.push(Message::System {
+                    content: format!("<schema>\n{}\n</schema>", schema),
+                });
+                self.answer_directly().await
+            }
+            Route::SmallTalk => self.answer_directly().await,
         }
+    }
 
+    /// Una sola generación, sin ejecutar SQL
+    async fn answer_directly(&mut self) -> Result<()> {
+        self.memory.push(Message::System {
+            content: self.router.direct_prompt.get().trim().to_string(),
+        });
+        self.run_agent(false, self.pipeline.direct_writer()).await?;
         Ok(())
     }
 
-    async fn run_agent(&mut self) -> Result<bool> {
+    async fn run_loop(&mut self) -> Result<()> {
+        let writer = self.pipeline.sql_writer();
+        let mut iterations = 0;
+        while iterations < self.max_iterations
+            && self
+                .run_agent(true, writer)
+                .instrument(tracing::info_span!(
+                    "agent_iteration",
+                    iteration = iterations
+                ))
+                .await?
+        {
+            iterations += 1;
+        }
+
+        if iterations >= self.max_iterations {
+            self.ui.notice(
+                Notice::Warning,
+                &format!("\n{}", t!("agent.iteration_limit")),
+            )?;
+        }
+
+        // Con el pipeline, la respuesta la redacta el escritor de respuestas
+        if writer == Writer::Sql && !self.cancel.is_cancelled() && self.pending.is_none() {
+            self.write_answer().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Respuesta del escritor de respuestas con los resultados del turno
+    async fn write_answer(&mut self) -> Result<()> {
+        // El cierre del escritor de SQL (sin query) no es parte de la respuesta
+        let closing = match self.memory.last() {
+            Some(Message::Assistant { content }) => sql::extract_sql(content).is_none(),
+            _ => false,
+        };
+        if closing {
+            self.memory.pop();
+        }
+
+        self.run_agent(false, Writer::Answer).await?;
+        Ok(())
+    }
+
+    /// Comprueba que los datos de la respuesta final aparezcan en los
+    /// resultados del turno. En modo `retry` se pide una corrección al modelo.
+    async fn verify_answer(&mut self, question: &str) -> Result<()> {
+        let answer = match self.memory.last() {
+            Some(Message::Assistant { content }) => content.clone(),
+            _ => return Ok(()),
+        };
+
+        let claims = verify::unsupported_claims(&answer, question, &self.turn_observed);
+        if claims.is_empty() {
+            return Ok(());
+        }
+
+        self.ui.notice(
+            Notice::Warning,
+            &format!("\n{}", t!("agent.unsupported", claims = claims.join(", "))),
+        )?;
+
+        if self.verification.mode != VerificationMode::Retry {
+            return Ok(());
+        }
+
+        let correction = Message::System {
+            content: format!(
+                "<verification>{}</verification>",
+                t!("prompt.verification", claims = claims.join(", "))
+            ),
+        };
+        self.memory.push(correction);
+        self.run_agent(false, self.pipeline.answer_writer()).await?;
+
+        // Una sola corrección por turno: después solo se avisa
+        let answer = match self.memory.last() {
+            Some(Message::Assistant { content }) => content.clone(),
+            _ => return Ok(()),
+        };
+        let claims = verify::unsupported_claims(&answer, question, &self.turn_observed);
+        if !claims.is_empty() {
+            self.ui.notice(
+                Notice::Warning,
+                &format!(
+                    "\n{}",
+                    t!("agent.still_unsupported", claims = claims.join(", "))
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    async fn run_agent(&mut self, allow_sql: bool, writer: Writer) -> Result<bool> {
         // Generate response
-        println!("{}", format!("\n─ {} ─", "Assistant").bright_cyan().bold());
-        stdout().flush()?;
+        self.ui.assistant_start()?;
+
+        let messages = self.pipeline.messages(writer, &self.memory);
+        let prompt_hash = audit::hash(&self.llm.get_template().render(&messages)?);
+
+        // El modelo genera en su propio hilo; aquí solo se muestran los eventos
+        let mut stream =
+            self.llm
+                .generate(&messages, &self.cancel, &self.pipeline.sampling(writer));
+        let mut completion = None;
+        // Una aclaración se muestra aparte, no según llega
+        let mut filter = StreamFilter::new(allow_sql && self.clarify.enabled);
+        while let Some(event) = stream.next().await {
+            match event? {
+                Event::ThinkingDelta(text) => self.ui.thinking_delta(&text)?,
+                Event::TextDelta(text) => {
+                    let visible = filter.push(&text);
+                    if !visible.is_empty() {
+                        self.ui.text_delta(&visible)?;
+                    }
+                }
+                Event::Finished(result) => completion = Some(result),
+            }
+        }
+        let rest = filter.finish();
+        if !rest.is_empty() {
+            self.ui.text_delta(&rest)?;
+        }
+        let completion = completion.ok_or_else(|| eyre!("{}", t!("agent.no_completion")))?;
+        self.debug.record(&completion)?;
+
+        self.ui.usage(&completion.usage)?;
 
-        let mut callback = PrintCallback::new(self.llm.get_tokenizer());
-        let assistant_message = self.llm.chat_stream(&self.memory, &mut callback)?;
-        self.memory.push(assistant_message.clone());
+        // El razonamiento no entra en la memoria, pero queda en las trazas
+        if let Some(reasoning) = &completion.reasoning {
+            tracing::debug!(reasoning = %reasoning, "Razonamiento del modelo");
+        }
 
-        let content = match assistant_message {
+        let content = match completion.message {
             Message::Assistant { content } => content,
             _ => "".into(),
         };
 
+        // Se conserva el texto parcial, marcado como interrumpido
+        if completion.stop_reason == StopReason::Cancelled {
+            self.memory.push(Message::Assistant {
+                content: format!("{}\n{}", content, t!("prompt.interrupted")),
+            });
+            return Ok(false);
+        }
+        self.memory.push(Message::Assistant {
+            content: content.clone(),
+        });
+
+        if !allow_sql {
+            return Ok(false);
+        }
+
+        match clarify::extract(&content) {
+            Some(clarification) if self.clarify.enabled => {
+                return self.ask_clarification(clarification);
+            }
+            _ => {}
+        }
+
         if let Some(sql) = sql::extract_sql(&content) {
-            println!("\n");
-            println!("{}", "🔍 Ejecutando SQL...".bright_yellow());
-            println!("{}", format!("   {}", sql.dimmed()));
+            self.ui.sql_start(&sql)?;
 
             if self.last_sql.as_ref() == Some(&sql) {
-                println!(
-                    "{}",
-                    "\n⚠️  El modelo está generando la misma query. Deteniendo ejecución de sql."
-                        .bright_yellow()
-                );
+                self.ui.notice(
+                    Notice::Warning,
+                    &format!("\n{}", t!("agent.repeated_query")),
+                )?;
                 // Agregar mensaje de ayuda al contexto
                 let hint_message = Message::System {
                     content: format!(
-                        "<sql_result>
-                        Ya ejecutaste esta query: {}\n\
-                        El resultado no fue el esperado. Intenta:\n\
-                        1. Una query DIFERENTE\n\
-                        2. Simplificar la query\n\
-                        3. Explicar al usuario que hay un problema con los datos
-                        </sql_result>",
-                        sql
+                        "<sql_result>\n{}\n</sql_result>",
+                        t!("prompt.repeated_query", sql = sql)
                     ),
                 };
                 self.memory.push(hint_message);
@@ -135,36 +690,259 @@ impl Agent {
             self.last_sql = Some(sql.clone());
 
             // Execute SQL
-            self.run_sql(&sql).await;
+            self.run_sql(&sql, chart::extract_kind(&content), &prompt_hash)
+                .await?;
 
-            return Ok(true);
+            return Ok(!self.cancel.is_cancelled());
         };
 
         Ok(false)
     }
 
-    async fn run_sql(&mut self, sql: &str) {
-        match sql::run_query(&self.db, &sql).await {
+    async fn run_sql(
+        &mut self,
+        sql: &str,
+        chart_kind: Option<ChartKind>,
+        prompt_hash: &str,
+    ) -> Result<()> {
+        let span = tracing::info_span!("run_query", sql, rows = Empty, latency_ms = Empty);
+        let started = Instant::now();
+
+        // Un resultado en caché no necesita plan ni ejecución
+        let cached = self.cache.get(sql);
+        let report = match cached {
+            Some(_) => PlanReport::default(),
+            None => self.analyze_plan(sql).await,
+        };
+        let refusal = self.plan.refusal(&report);
+        let from_cache = cached.is_some();
+        let outcome = match (cached, &refusal) {
+            (Some(results), _) => Ok(results),
+            (None, Some(reason)) => Err(eyre!("{}", reason)),
+            (None, None) => tokio::select! {
+                outcome = sql::run_query(&self.db, sql).instrument(span.clone()) => outcome,
+                _ = self.cancel.cancelled() => Err(eyre!("{}", t!("agent.query_cancelled"))),
+            },
+        };
+        let latency = started.elapsed();
+        let block_reason = sql::check_query(sql).or(refusal.as_deref());
+        self.last_query = Some(sql.to_string());
+
+        span.record("latency_ms", latency.as_millis() as u64);
+        if let Ok(results) = &outcome {
+            span.record("rows", results["count"].as_u64());
+        }
+
+        self.audit.log(AuditEvent::Query {
+            question: &self.turn_question,
+            prompt_hash,
+            sql,
+            blocked: block_reason.is_some(),
+            block_reason,
+            rows: outcome.as_ref().ok().and_then(|r| r["count"].as_u64()),
+            cached: from_cache,
+            duration_ms: latency.as_millis(),
+            error: outcome.as_ref().err().map(|err| err.to_string()),
+        })?;
+
+        match outcome {
             Ok(results) => {
-                let formatted = sql::format_results(&results);
-                println!("{}", "✅ Query ejecutada".bright_green());
-                println!("{}", formatted.dimmed());
+                let formatted = self.format_results(&results);
+                self.ui.sql_result(&results, &formatted, from_cache)?;
+                let stored = if from_cache {
+                    Ok(())
+                } else {
+                    self.cache.insert(sql, &results)
+                };
+                if let Err(err) = stored {
+                    tracing::warn!("⚠️  No se pudo guardar en la caché: {}", err);
+                }
+
+                self.last_chart = chart::detect(&results, chart_kind);
+                if let Some(chart) = &self.last_chart {
+                    self.ui.chart(&chart.render_text())?;
+                }
+
+                // Valores con aspecto de instrucciones: se avisan y, en modo
+                // redact, el modelo no llega a verlos
+                let findings = self.guard.scan(&results);
+                let redacted = self.guard.redact(&results, &findings);
+                if !findings.is_empty() {
+                    self.report_injection(sql, &findings, redacted.is_some())?;
+                }
+                let formatted = match &redacted {
+                    Some(redacted) => self.format_results(redacted),
+                    None => formatted,
+                };
+
                 // Add results to memory for model context
                 let result_message = Message::System {
-                    content: format!("<sql_result>\n{}\n</sql_result>", formatted),
+                    content: self.guard.wrap("sql_result", &formatted, &findings)?,
                 };
 
                 self.memory.push(result_message);
+                self.turn_results.push(redacted.unwrap_or(results));
+                self.turn_observed.push(Observed {
+                    sql: sql.to_string(),
+                    rendered: formatted,
+                });
+                self.turn_sql = Some(sql.to_string());
+
+                let hints = report.hints();
+                if self.plan.gives_hints() && !hints.is_empty() {
+                    self.memory.push(Message::System {
+                        content: format!("<plan_hint>\n{}\n</plan_hint>", hints.join("\n")),
+                    });
+                }
             }
             Err(err) => {
-                println!("{}", format!("❌ Error: {}", err).bright_red());
+                self.ui
+                    .notice(Notice::Error, &t!("agent.error", error = err))?;
 
                 // Add error to memory
                 let error_message = Message::System {
-                    content: format!("<sql_error>{}</sql_error>", err),
+                    content: self.guard.wrap("sql_error", &err.to_string(), &[])?,
                 };
                 self.memory.push(error_message);
             }
         };
+
+        Ok(())
+    }
+}
+
+impl Agent {
+    /// Plan de la query antes de ejecutarla; los problemas se avisan al usuario
+    async fn analyze_plan(&mut self, sql: &str) -> PlanReport {
+        if !self.plan.enabled() || sql::check_query(sql).is_some() {
+            return PlanReport::default();
+        }
+
+        // Si el plan falla (p. ej. error de sintaxis), la ejecución dará el error
+        let report = match self.plan.analyze(&self.db, sql).await {
+            Ok(report) => report,
+            Err(err) => {
+                tracing::debug!("No se pudo analizar el plan: {}", err);
+                return PlanReport::default();
+            }
+        };
+
+        for issue in &report.issues {
+            let notice = t!("agent.plan_issue", issue = issue.message());
+            if let Err(err) = self.ui.notice(Notice::Warning, &notice) {
+                tracing::debug!("No se pudo mostrar el aviso: {}", err);
+            }
+        }
+        report
+    }
+
+    fn report_injection(&mut self, sql: &str, findings: &[Finding], redacted: bool) -> Result<()> {
+        let columns = guard::columns(findings);
+        let notice = if redacted {
+            t!(
+                "agent.injection_redacted",
+                count = findings.len(),
+                columns = columns
+            )
+        } else {
+            t!("agent.injection", count = findings.len(), columns = columns)
+        };
+        self.ui.notice(Notice::Warning, &notice)?;
+        tracing::warn!(
+            ?findings,
+            "Posible inyección de instrucciones en los resultados"
+        );
+
+        self.audit.log(AuditEvent::Injection {
+            question: &self.turn_question,
+            sql,
+            findings,
+            redacted,
+        })
     }
+
+    /// Pausa el bucle SQL hasta que el usuario responda. Agotadas las
+    /// aclaraciones, el modelo tiene que responder con su mejor interpretación
+    fn ask_clarification(&mut self, clarification: Clarification) -> Result<bool> {
+        if self.clarify_rounds >= self.clarify.max_rounds {
+            self.memory.push(Message::System {
+                content: format!(
+                    "<clarify_limit>{}</clarify_limit>",
+                    t!("prompt.clarify_limit")
+                ),
+            });
+            return Ok(true);
+        }
+
+        self.ui
+            .clarification(&clarification.question, &clarification.options)?;
+        self.pending = Some(Pending {
+            question: self.turn_question.clone(),
+            options: clarification.options,
+            rounds: self.clarify_rounds + 1,
+        });
+        Ok(false)
+    }
+
+    /// Resultados como texto para el modelo, dentro del presupuesto de tokens
+    fn format_results(&self, results: &Value) -> String {
+        let tokenizer = self.llm.get_tokenizer();
+        let count_tokens = |text: &str| {
+            tokenizer
+                .encode(text, false)
+                .map(|encoding| encoding.len())
+                .unwrap_or(text.len() / 4)
+        };
+        sql::format_results(results, &self.results, &count_tokens)
+    }
+
+    /// `/sql <query>` y `/rerun`: ejecuta una query escrita por el usuario,
+    /// sin pasar por el modelo ni añadir el resultado a la memoria
+    async fn run_user_sql(&mut self, sql: &str) -> Result<()> {
+        self.ui.sql_start(sql)?;
+        let started = Instant::now();
+
+        self.cancel.start();
+        let outcome = tokio::select! {
+            outcome = sql::run_query(&self.db, sql) => outcome,
+            _ = self.cancel.cancelled() => Err(eyre!("{}", t!("agent.query_cancelled"))),
+        };
+        self.cancel.finish();
+        let latency = started.elapsed();
+        let block_reason = sql::check_query(sql);
+        self.last_query = Some(sql.to_string());
+
+        self.audit.log(AuditEvent::Query {
+            question: "/sql",
+            prompt_hash: "",
+            sql,
+            blocked: block_reason.is_some(),
+            block_reason,
+            rows: outcome.as_ref().ok().and_then(|r| r["count"].as_u64()),
+            cached: false,
+            duration_ms: latency.as_millis(),
+            error: outcome.as_ref().err().map(|err| err.to_string()),
+        })?;
+
+        match outcome {
+            Ok(results) => {
+                let formatted = self.format_results(&results);
+                self.ui.sql_result(&results, &formatted, false)?;
+                self.last_chart = chart::detect(&results, None);
+                if let Some(chart) = &self.last_chart {
+                    self.ui.chart(&chart.render_text())?;
+                }
+                Ok(())
+            }
+            Err(err) => self
+                .ui
+                .notice(Notice::Error, &t!("agent.error", error = err)),
+        }
+    }
+}
+
+/// Identificador de sesión: instante de inicio y pid del proceso
+fn new_session_id() -> Result<String> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    Ok(format!("{:x}-{}", millis, std::process::id()))
 }
@@ -0,0 +1,233 @@
+use color_eyre::Result;
+
+use crate::{
+    chat::Message,
+    config::{PipelineConfig, SamplingOverride},
+};
+
+/// Contexto que solo necesita el escritor de SQL: ejemplos, esquema, la
+/// query anterior y las pistas del plan
+const SQL_CONTEXT: [&str; 4] = ["<examples>", "<schema>", "<previous_query>", "<plan_hint>"];
+
+/// Quién genera cada respuesta del modelo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Writer {
+    /// Un solo prompt escribe el SQL y la respuesta (sin pipeline)
+    Single,
+    /// Escribe y corrige las queries
+    Sql,
+    /// Redacta la respuesta a partir de los resultados
+    Answer,
+    /// Responde sin consultar la base de datos (esquema, charla), con el
+    /// system prompt general y el muestreo de las respuestas
+    Direct,
+}
+
+/// Escritor de SQL y escritor de respuestas sobre el mismo modelo. Cada uno
+/// recibe la memoria de la conversación con su propio system prompt y genera
+/// con sus parámetros de muestreo.
+pub(super) struct Pipeline {
+    enabled: bool,
+    sql_prompt: String,
+    answer_prompt: String,
+    sql_sampling: SamplingOverride,
+    answer_sampling: SamplingOverride,
+}
+
+impl Pipeline {
+    /// Las plantillas usan el mismo contexto que el system prompt. Las
+    /// aclaraciones las pide el escritor de SQL
+    pub fn new(
+        cfg: &PipelineConfig,
+        context: &tera::Context,
+        clarify: Option<&str>,
+    ) -> Result<Self> {
+        let (sql_prompt, answer_prompt) = if cfg.enabled {
+            (
+                tera::Tera::one_off(cfg.sql_prompt.get(), context, false)?,
+                tera::Tera::one_off(cfg.answer_prompt.get(), context, false)?,
+            )
+        } else {
+            (String::new(), String::new())
+        };
+        let sql_prompt = match clarify {
+            Some(clarify) => format!("{}\n\n{}", sql_prompt.trim_end(), clarify),
+            None => sql_prompt,
+        };
+
+        Ok(Self {
+            enabled: cfg.enabled,
+            sql_prompt,
+            answer_prompt,
+            sql_sampling: cfg.sql_sampling,
+            answer_sampling: cfg.answer_sampling,
+        })
+    }
+
+    /// Escritor del bucle SQL
+    pub fn sql_writer(&self) -> Writer {
+        if self.enabled {
+            Writer::Sql
+        } else {
+            Writer::Single
+        }
+    }
+
+    /// Escritor de la respuesta con los resultados del turno
+    pub fn answer_writer(&self) -> Writer {
+        if self.enabled {
+            Writer::Answer
+        } else {
+            Writer::Single
+        }
+    }
+
+    /// Escritor de las rutas que no consultan datos. El prompt del escritor
+    /// de respuestas da por hecho que las queries ya se ejecutaron
+    pub fn direct_writer(&self) -> Writer {
+        if self.enabled {
+            Writer::Direct
+        } else {
+            Writer::Single
+        }
+    }
+
+    /// Memoria con el system prompt del escritor en lugar del general. El
+    /// escritor de respuestas no recibe el contexto SQL, solo los resultados
+    pub fn messages(&self, writer: Writer, memory: &[Message]) -> Vec<Message> {
+        let prompt = match writer {
+            Writer::Single | Writer::Direct => return memory.to_vec(),
+            Writer::Sql => &self.sql_prompt,
+            Writer::Answer => &self.answer_prompt,
+        };
+
+        let mut messages: Vec<Message> = memory
+            .iter()
+            .filter(|msg| match (writer, msg) {
+                (Writer::Answer, Message::System { content }) => {
+                    !SQL_CONTEXT.iter().any(|tag| content.starts_with(tag))
+                }
+                _ => true,
+            })
+            .cloned()
+            .collect();
+        if let Some(first @ Message::System { .. }) = messages.first_mut() {
+            *first = Message::System {
+                content: prompt.clone(),
+            };
+        }
+        messages
+    }
+
+    pub fn sampling(&self, writer: Writer) -> SamplingOverride {
+        match writer {
+            Writer::Single => SamplingOverride::default(),
+            Writer::Sql => self.sql_sampling,
+            Writer::Answer | Writer::Direct => self.answer_sampling,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::Localized;
+
+    fn pipeline() -> Pipeline {
+        let prompt = |text: &str| Localized {
+            es: text.to_string(),
+            en: text.to_string(),
+        };
+        let cfg = PipelineConfig {
+            enabled: true,
+            sql_prompt: prompt("SQL para {{ database }}"),
+            answer_prompt: prompt("Las queries ya se han ejecutado"),
+            sql_sampling: SamplingOverride::default(),
+            answer_sampling: SamplingOverride {
+                temperature: Some(0.8),
+                ..SamplingOverride::default()
+            },
+        };
+        let mut context = tera::Context::new();
+        context.insert("database", "sakila");
+        Pipeline::new(&cfg, &context, None).unwrap()
+    }
+
+    fn system_prompt(messages: &[Message]) -> &str {
+        match messages.first() {
+            Some(Message::System { content }) => content,
+            _ => "",
+        }
+    }
+
+    #[test]
+    fn each_writer_gets_its_prompt() {
+        let pipeline = pipeline();
+        let memory = vec![
+            Message::System {
+                content: "general".to_string(),
+            },
+            Message::User {
+                content: "hola".to_string(),
+            },
+        ];
+
+        let sql = pipeline.messages(pipeline.sql_writer(), &memory);
+        assert_eq!(system_prompt(&sql), "SQL para sakila");
+        let answer = pipeline.messages(pipeline.answer_writer(), &memory);
+        assert_eq!(system_prompt(&answer), "Las queries ya se han ejecutado");
+        assert_eq!(answer.len(), memory.len());
+    }
+
+    #[test]
+    fn answer_writer_gets_no_sql_context() {
+        let pipeline = pipeline();
+        let system = |content: &str| Message::System {
+            content: content.to_string(),
+        };
+        let memory = vec![
+            system("general"),
+            system("<examples>\nSELECT 1\n</examples>"),
+            Message::User {
+                content: "¿y en 2006?".to_string(),
+            },
+            system("<previous_query>SELECT COUNT(*) FROM rental</previous_query>"),
+            system("<sql_result>\n15862\n</sql_result>"),
+        ];
+
+        let sql = pipeline.messages(pipeline.sql_writer(), &memory);
+        assert_eq!(sql.len(), memory.len());
+
+        let answer = pipeline.messages(pipeline.answer_writer(), &memory);
+        let blocks: Vec<&str> = answer
+            .iter()
+            .filter_map(|msg| match msg {
+                Message::System { content } => Some(content.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                "Las queries ya se han ejecutado",
+                "<sql_result>\n15862\n</sql_result>"
+            ]
+        );
+        assert_eq!(answer.len(), 3);
+    }
+
+    #[test]
+    fn direct_answers_use_the_general_prompt() {
+        let pipeline = pipeline();
+        let memory = vec![Message::System {
+            content: "general".to_string(),
+        }];
+
+        let writer = pipeline.direct_writer();
+        assert_eq!(
+            system_prompt(&pipeline.messages(writer, &memory)),
+            "general"
+        );
+        assert_eq!(pipeline.sampling(writer).temperature, Some(0.8));
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use color_eyre::Result;
+use regex::Regex;
+
+use crate::{
+    config::{PlanConfig, PlanMode},
+    db::{Database, Dialect},
+    t,
+};
+
+/// Palabras que pueden seguir a una tabla y no son su alias
+const KEYWORDS: [&str; 16] = [
+    "where", "join", "on", "using", "left", "right", "inner", "outer", "full", "cross", "natural",
+    "group", "order", "limit", "union", "having",
+];
+
+/// Problema detectado en el plan de una query
+#[derive(Debug)]
+pub(super) enum Issue {
+    /// Lectura completa de una tabla grande
+    FullScan { table: String, rows: u64 },
+    /// Producto cartesiano entre tablas (FROM a, b sin WHERE, CROSS JOIN...)
+    Cartesian { tables: Vec<String> },
+    /// JOIN sin ON ni USING
+    MissingJoinCondition { table: String },
+}
+
+impl Issue {
+    pub fn message(&self) -> String {
+        match self {
+            Issue::FullScan { table, rows } => {
+                t!("plan.full_scan", table = table, rows = rows)
+            }
+            Issue::Cartesian { tables } => {
+                t!("plan.cartesian", tables = tables.join(", "))
+            }
+            Issue::MissingJoinCondition { table } => {
+                t!("plan.missing_join", table = table)
+            }
+        }
+    }
+
+    /// Tablas que multiplican las filas del resto de la query: en un producto
+    /// cartesiano, todas menos la primera
+    fn unjoined(&self) -> Vec<String> {
+        match self {
+            Issue::FullScan { .. } => Vec::new(),
+            Issue::Cartesian { tables } => tables.iter().skip(1).cloned().collect(),
+            Issue::MissingJoinCondition { table } => vec![table.clone()],
+        }
+    }
+
+    /// Indicación para el modelo. Las lecturas completas no la llevan:
+    /// muchas agregaciones las necesitan
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Issue::FullScan { .. } => None,
+            Issue::Cartesian { tables } => {
+                Some(t!("plan.cartesian_hint", tables = tables.join(", ")))
+            }
+            Issue::MissingJoinCondition { table } => {
+                Some(t!("plan.missing_join_hint", table = table))
+            }
+        }
+    }
+}
+
+/// Resultado del análisis: problemas y coste estimado en filas leídas
+#[derive(Debug, Default)]
+pub(super) struct PlanReport {
+    pub issues: Vec<Issue>,
+    pub cost: u64,
+}
+
+impl PlanReport {
+    pub fn hints(&self) -> Vec<String> {
+        self.issues.iter().filter_map(Issue::hint).collect()
+    }
+}
+
+/// Analiza el plan de cada query antes de ejecutarla
+pub(super) struct PlanAnalyzer {
+    config: PlanConfig,
+    /// Filas por tabla, contadas una sola vez por sesión
+    table_rows: HashMap<String, u64>,
+}
+
+impl PlanAnalyzer {
+    pub fn new(config: &PlanConfig) -> Self {
+        Self {
+            config: config.clone(),
+            table_rows: HashMap::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// En modo `hint` los problemas también se le indican al modelo
+    pub fn gives_hints(&self) -> bool {
+        self.config.mode == PlanMode::Hint
+    }
+
+    /// Motivo para no ejecutar la query, si su coste supera el máximo
+    pub fn refusal(&self, report: &PlanReport) -> Option<String> {
+        let max_cost = self.config.max_cost?;
+        if report.cost <= max_cost {
+            return None;
+        }
+
+        let mut reason = t!("plan.refused", cost = report.cost, max = max_cost);
+        for hint in report.hints() {
+            reason.push_str(&format!("\n- {}", hint));
+        }
+        Some(reason)
+    }
+
+    pub async fn analyze(&mut self, db: &Database, query: &str) -> Result<PlanReport> {
+        let plan = db.explain(query).await?;
+        let aliases = table_aliases(query);
+        let resolve = |name: &str| {
+            aliases
+                .iter()
+                .find(|(_, alias)| alias.as_deref() == Some(name))
+                .map_or(name.to_string(), |(table, _)| table.clone())
+        };
+
+        let mut issues = Vec::new();
+
+        // Lecturas completas de tablas grandes. Con agregados o LIMIT son
+        // lo esperado y solo cuentan para el coste
+        let mut scanned = Vec::new();
+        let bounded = is_bounded(query);
+        for table in scanned_tables(db.dialect(), &plan) {
+            let table = resolve(&table);
+            let rows = self.rows(db, &table).await;
+            if rows >= self.config.large_table_rows && !bounded {
+                issues.push(Issue::FullScan {
+                    table: table.clone(),
+                    rows,
+                });
+            }
+            scanned.push((table, rows));
+        }
+
+        // Combinaciones de tablas sin condición. Si DuckDB ve un producto que
+        // el texto no muestra, no se sabe qué tablas combina: cuentan todas
+        let joins = join_issues(query);
+        if joins.is_empty()
+            && db.dialect() == Dialect::DuckDb
+            && plan.iter().any(|line| line.contains("CROSS_PRODUCT"))
+        {
+            let tables = aliases.iter().map(|(table, _)| table.clone()).collect();
+            issues.push(Issue::Cartesian { tables });
+        }
+        issues.extend(joins);
+
+        // Coste: filas leídas por las tablas bien combinadas, multiplicadas por
+        // las de cada tabla que se añade sin condición
+        let mut unjoined: Vec<String> = issues.iter().flat_map(Issue::unjoined).collect();
+        unjoined.sort();
+        unjoined.dedup();
+
+        let mut cost: u64 = scanned
+            .iter()
+            .filter(|(table, _)| !unjoined.contains(table))
+            .map(|(_, rows)| rows)
+            .sum();
+        if !unjoined.is_empty() {
+            cost = cost.max(1);
+            for table in &unjoined {
+                cost = cost.saturating_mul(self.rows(db, table).await.max(1));
+            }
+        }
+
+        Ok(PlanReport { issues, cost })
+    }
+
+    async fn rows(&mut self, db: &Database, table: &str) -> u64 {
+        if let Some(rows) = self.table_rows.get(table) {
+            return *rows;
+        }
+
+        let count = db
+            .query(&format!("SELECT COUNT(*) AS n FROM \"{}\"", table))
+            .await
+            .ok()
+            .and_then(|rows| rows.first().and_then(|row| row["n"].as_u64()))
+            .unwrap_or(0);
+        self.table_rows.insert(table.to_string(), count);
+        count
+    }
+}
+
+/// Tablas leídas enteras según el plan de cada backend
+fn scanned_tables(dialect: Dialect, plan: &[String]) -> Vec<String> {
+    let pattern = match dialect {
+        // "SCAN film", "SCAN f USING COVERING INDEX ..." (o "SCAN TABLE film AS f")
+        Dialect::Sqlite => r"^SCAN (?:TABLE )?(\w+)(?: AS (\w+))?",
+        // "Seq Scan on film f  (cost=...)"
+        Dialect::Postgres => r"Seq Scan on (?:\w+\.)?(\w+)",
+        // Diagrama de DuckDB: "Table: film" dentro de un nodo SEQ_SCAN
+        Dialect::DuckDb => r"Table:\s*(\w+)",
+    };
+    let Ok(re) = Regex::new(pattern) else {
+        return Vec::new();
+    };
+
+    plan.iter()
+        .filter_map(|line| re.captures(line.trim().trim_matches('│').trim()))
+        .filter_map(|captures| captures.get(2).or(captures.get(1)))
+        .map(|name| name.as_str().to_string())
+        .filter(|name| name != "CONSTANT")
+        .collect()
+}
+
+/// (tabla, alias) de las cláusulas FROM y JOIN
+fn table_aliases(query: &str) -> Vec<(String, Option<String>)> {
+    let (Ok(table), Ok(alias)) = (
+        Regex::new(r"(?i)\b(?:FROM|JOIN)\s+(\w+)"),
+        Regex::new(r"(?i)^\s+(?:AS\s+)?(\w+)"),
+    ) else {
+        return Vec::new();
+    };
+
+    // El alias se busca aparte para no consumir un JOIN que venga detrás
+    table
+        .captures_iter(query)
+        .filter(|captures| {
+            captures
+                .get(0)
+                .is_some_and(|m| !in_function(query, m.start()))
+        })
+        .filter_map(|captures| {
+            let name = captures.get(1)?;
+            let alias = alias
+                .captures(&query[name.end()..])
+                .map(|alias| alias[1].to_string())
+                .filter(|alias| !KEYWORDS.contains(&alias.to_lowercase().as_str()));
+            Some((name.as_str().to_string(), alias))
+        })
+        .collect()
+}
+
+/// Un FROM entre los paréntesis de una función (`EXTRACT(YEAR FROM x)`,
+/// `SUBSTRING(x FROM 1)`) no introduce una tabla; en una subconsulta sí
+fn in_function(query: &str, at: usize) -> bool {
+    let mut open = Vec::new();
+    for (i, c) in query[..at].char_indices() {
+        match c {
+            '(' => open.push(i),
+            ')' => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+
+    open.last().is_some_and(|&start| {
+        let inside = query[start + 1..at].trim_start().to_uppercase();
+        !inside.starts_with("SELECT") && !inside.starts_with("WITH")
+    })
+}
+
+/// Agregados, GROUP BY o LIMIT: la query no devuelve la tabla entera
+fn is_bounded(query: &str) -> bool {
+    Regex::new(
+        r"(?i)\b(?:COUNT|SUM|AVG|MIN|MAX|TOTAL|GROUP_CONCAT|STRING_AGG)\s*\(|\bGROUP\s+BY\b|\bLIMIT\b",
+    )
+    .is_ok_and(|re| re.is_match(query))
+}
+
+/// JOIN sin ON/USING, CROSS JOIN y `FROM a, b` sin WHERE
+fn join_issues(query: &str) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let (Ok(join), Ok(condition), Ok(comma)) = (
+        Regex::new(r"(?i)\b(NATURAL\s+|CROSS\s+)?JOIN\s+(\w+)"),
+        Regex::new(r"(?i)\b(ON|USING)\b"),
+        Regex::new(r"(?i)\bFROM\s+(\w+)(?:\s+(?:AS\s+)?\w+)?\s*,\s*(\w+)"),
+    ) else {
+        return issues;
+    };
+
+    let joins: Vec<_> = join.captures_iter(query).collect();
+    for (i, captures) in joins.iter().enumerate() {
+        let table = captures[2].to_string();
+        let modifier = captures.get(1).map(|m| m.as_str().trim().to_uppercase());
+        match modifier.as_deref() {
+            Some("NATURAL") => continue,
+            // Se combina con la tabla anterior, no con toda la query
+            Some("CROSS") => {
+                let at = captures.get(0).map_or(0, |m| m.start());
+                let previous = table_aliases(&query[..at])
+                    .pop()
+                    .map(|(table, _)| table)
+                    .unwrap_or_default();
+                issues.push(Issue::Cartesian {
+                    tables: vec![previous, table],
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        // Texto entre este JOIN y el siguiente: ahí debería estar su ON
+        let start = captures.get(0).map_or(0, |m| m.end());
+        let end = joins
+            .get(i + 1)
+            .and_then(|next| next.get(0))
+            .map_or(query.len(), |m| m.start());
+        if !condition.is_match(&query[start..end]) {
+            issues.push(Issue::MissingJoinCondition { table });
+        }
+    }
+
+    let has_where = Regex::new(r"(?i)\bWHERE\b").is_ok_and(|re| re.is_match(query));
+    let listed = comma.captures_iter(query).find(|captures| {
+        captures
+            .get(0)
+            .is_some_and(|m| !in_function(query, m.start()))
+    });
+    match listed {
+        Some(captures) if !has_where => issues.push(Issue::Cartesian {
+            tables: vec![captures[1].to_string(), captures[2].to_string()],
+        }),
+        _ => {}
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables(query: &str) -> Vec<String> {
+        table_aliases(query)
+            .into_iter()
+            .map(|(table, _)| table)
+            .collect()
+    }
+
+    #[test]
+    fn from_inside_functions_is_not_a_table() {
+        assert_eq!(
+            tables("SELECT EXTRACT(YEAR FROM rental_date) AS y, COUNT(*) FROM rental GROUP BY y"),
+            vec!["rental"]
+        );
+        assert_eq!(
+            tables(
+                "SELECT SUBSTRING(title FROM 1 FOR 3) FROM film f JOIN inventory i ON f.film_id = i.film_id"
+            ),
+            vec!["film", "inventory"]
+        );
+        assert_eq!(
+            tables("SELECT TRIM(BOTH ' ' FROM first_name) FROM actor"),
+            vec!["actor"]
+        );
+    }
+
+    #[test]
+    fn subqueries_are_tables() {
+        assert_eq!(
+            tables("SELECT title FROM film WHERE film_id IN (SELECT film_id FROM inventory)"),
+            vec!["film", "inventory"]
+        );
+        assert_eq!(
+            tables("SELECT * FROM (SELECT EXTRACT(YEAR FROM payment_date) AS y FROM payment) p"),
+            vec!["payment"]
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(
+            table_aliases(
+                "SELECT * FROM film AS f JOIN language l ON f.language_id = l.language_id WHERE 1"
+            ),
+            vec![
+                ("film".to_string(), Some("f".to_string())),
+                ("language".to_string(), Some("l".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn comma_inside_functions_is_not_cartesian() {
+        let issues = join_issues("SELECT EXTRACT(YEAR FROM rental_date), COUNT(*) FROM rental");
+        assert!(issues.is_empty(), "{:?}", issues);
+
+        let issues = join_issues("SELECT * FROM film, actor");
+        assert!(matches!(issues.as_slice(), [Issue::Cartesian { .. }]));
+    }
+
+    /// rental y payment (1000 filas cada una) y category (16), en memoria
+    async fn database() -> Database {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::raw_sql(
+            "CREATE TABLE rental (rental_id INTEGER PRIMARY KEY, customer_id INTEGER);
+             CREATE TABLE payment (payment_id INTEGER PRIMARY KEY, rental_id INTEGER, amount REAL);
+             CREATE TABLE category (category_id INTEGER PRIMARY KEY, name TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 1000)
+             INSERT INTO rental SELECT i, i % 50 FROM n;
+             INSERT INTO payment SELECT rental_id, rental_id, 1.5 FROM rental;
+             INSERT INTO category SELECT rental_id, 'c' FROM rental WHERE rental_id <= 16;",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        Database::Sqlite(pool)
+    }
+
+    fn analyzer() -> PlanAnalyzer {
+        PlanAnalyzer::new(&PlanConfig {
+            enabled: true,
+            large_table_rows: 10_000,
+            max_cost: Some(100_000),
+            mode: PlanMode::Hint,
+        })
+    }
+
+    #[tokio::test]
+    async fn joined_tables_are_not_multiplied() {
+        let db = database().await;
+        let mut analyzer = analyzer();
+
+        let joined = "SELECT r.customer_id, p.amount FROM rental r \
+                      JOIN payment p ON p.rental_id = r.rental_id";
+        let report = analyzer.analyze(&db, joined).await.unwrap();
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert!(report.cost <= 2_000, "{}", report.cost);
+
+        // La tabla pequeña sin condición multiplica las filas del par, no
+        // el producto de las tres tablas (16 millones)
+        let query = format!("{} CROSS JOIN category c", joined);
+        let report = analyzer.analyze(&db, &query).await.unwrap();
+        assert!(matches!(
+            report.issues.as_slice(),
+            [Issue::Cartesian { tables }] if tables == &["payment", "category"]
+        ));
+        assert!(report.cost <= 2_000 * 16, "{}", report.cost);
+        assert!(analyzer.refusal(&report).is_none());
+
+        let query = format!("{} JOIN category c", joined);
+        let report = analyzer.analyze(&db, &query).await.unwrap();
+        assert!(report.cost <= 2_000 * 16, "{}", report.cost);
+    }
+
+    #[tokio::test]
+    async fn cartesian_pairs_are_multiplied() {
+        let db = database().await;
+        let mut analyzer = analyzer();
+
+        let report = analyzer
+            .analyze(&db, "SELECT * FROM rental, payment")
+            .await
+            .unwrap();
+        assert_eq!(report.cost, 1_000 * 1_000);
+        assert!(analyzer.refusal(&report).is_some());
+    }
+
+    #[test]
+    fn bounded_queries() {
+        assert!(is_bounded("SELECT COUNT(*) FROM rental"));
+        assert!(is_bounded(
+            "SELECT rating, AVG(length) FROM film GROUP BY rating"
+        ));
+        assert!(is_bounded("SELECT * FROM payment LIMIT 10"));
+        assert!(!is_bounded("SELECT * FROM payment"));
+        assert!(!is_bounded(
+            "SELECT amount FROM payment WHERE customer_id = 1"
+        ));
+    }
+}
@@ -0,0 +1,254 @@
+use std::path::Path;
+
+use color_eyre::{Result, eyre::eyre};
+use regex::Regex;
+use serde_json::Value;
+
+use super::{chart::Chart, digest::value_to_string, guard::escape_markup};
+use crate::t;
+
+/// Filas de cada resultado que se incluyen en el informe
+const MAX_ROWS: usize = 50;
+
+/// Palabras clave resaltadas en el SQL del informe HTML
+const SQL_KEYWORDS: &str = r"(?i)\b(SELECT|FROM|WHERE|JOIN|LEFT|RIGHT|INNER|OUTER|CROSS|ON|USING|AS|AND|OR|NOT|IN|IS|NULL|LIKE|ILIKE|BETWEEN|GROUP|BY|ORDER|HAVING|LIMIT|OFFSET|DISTINCT|UNION|ALL|WITH|CASE|WHEN|THEN|ELSE|END|ASC|DESC|COUNT|SUM|AVG|MIN|MAX)\b";
+
+/// Un turno de la sesión tal como aparece en el informe
+#[derive(Debug, Clone)]
+pub(super) struct ReportEntry {
+    pub question: String,
+    pub answer: String,
+    /// Query que produjo la respuesta, si la hubo
+    pub sql: Option<String>,
+    pub result: Option<Value>,
+    pub chart: Option<Chart>,
+}
+
+/// Escribe el informe en Markdown o HTML según la extensión del fichero.
+/// En Markdown los gráficos se guardan como SVG junto al informe
+pub(super) fn write(path: &Path, database: &str, entries: &[ReportEntry]) -> Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let dir = path.parent().unwrap_or(Path::new(""));
+    if !dir.as_os_str().is_empty() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let document = match extension.as_str() {
+        "md" | "markdown" => {
+            let stem = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("report");
+            let mut charts = Vec::new();
+            let document = markdown(database, entries, stem, &mut charts);
+            for (file, svg) in charts {
+                std::fs::write(dir.join(file), svg)?;
+            }
+            document
+        }
+        "html" | "htm" => html(database, entries),
+        _ => return Err(eyre!("{}", t!("report.unsupported"))),
+    };
+
+    std::fs::write(path, document)?;
+    Ok(())
+}
+
+fn markdown(
+    database: &str,
+    entries: &[ReportEntry],
+    stem: &str,
+    charts: &mut Vec<(String, String)>,
+) -> String {
+    let mut output = format!(
+        "# {}\n\n{}: `{}`\n",
+        t!("report.title"),
+        t!("report.database"),
+        database
+    );
+
+    for (i, entry) in entries.iter().enumerate() {
+        output.push_str(&format!("\n## {}. {}\n\n", i + 1, entry.question));
+        output.push_str(&format!("{}\n", entry.answer.trim()));
+
+        if let Some(sql) = &entry.sql {
+            output.push_str(&format!("\n```sql\n{}\n```\n", sql.trim()));
+        }
+
+        if let Some(result) = &entry.result {
+            let (columns, rows) = table(result);
+            if !columns.is_empty() {
+                let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+                let header: Vec<String> = columns.iter().map(|c| cell(c)).collect();
+                output.push_str(&format!("\n| {} |\n", header.join(" | ")));
+                output.push_str(&format!("|{}\n", " --- |".repeat(columns.len())));
+                for row in &rows {
+                    let row: Vec<String> = row.iter().map(|c| cell(c)).collect();
+                    output.push_str(&format!("| {} |\n", row.join(" | ")));
+                }
+                if let Some(note) = truncation_note(result) {
+                    output.push_str(&format!("\n_{}_\n", note));
+                }
+            }
+        }
+
+        if let Some(chart) = &entry.chart {
+            let file = format!("{}-{}.svg", stem, i + 1);
+            output.push_str(&format!(
+                "\n![{}]({})\n",
+                t!("report.chart", n = i + 1),
+                file
+            ));
+            charts.push((file, chart.render_svg()));
+        }
+    }
+
+    output
+}
+
+fn html(database: &str, entries: &[ReportEntry]) -> String {
+    let mut body = String::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        body.push_str("<section>\n");
+        body.push_str(&format!(
+            "<h2>{}. {}</h2>\n",
+            i + 1,
+            escape_markup(&entry.question)
+        ));
+        for paragraph in entry.answer.trim().split("\n\n") {
+            body.push_str(&format!("<p>{}</p>\n", escape_markup(paragraph)));
+        }
+
+        if let Some(sql) = &entry.sql {
+            body.push_str(&format!(
+                "<pre><code class=\"sql\">{}</code></pre>\n",
+                highlight_sql(sql.trim())
+            ));
+        }
+
+        if let Some(result) = &entry.result {
+            let (columns, rows) = table(result);
+            if !columns.is_empty() {
+                body.push_str("<table>\n<tr>");
+                for column in &columns {
+                    body.push_str(&format!("<th>{}</th>", escape_markup(column)));
+                }
+                body.push_str("</tr>\n");
+                for row in &rows {
+                    body.push_str("<tr>");
+                    for cell in row {
+                        body.push_str(&format!("<td>{}</td>", escape_markup(cell)));
+                    }
+                    body.push_str("</tr>\n");
+                }
+                body.push_str("</table>\n");
+                if let Some(note) = truncation_note(result) {
+                    body.push_str(&format!("<p class=\"note\">{}</p>\n", note));
+                }
+            }
+        }
+
+        if let Some(chart) = &entry.chart {
+            body.push_str(&format!("<figure>\n{}</figure>\n", chart.render_svg()));
+        }
+        body.push_str("</section>\n");
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+  body {{ font-family: sans-serif; max-width: 900px; margin: 2em auto; color: #222; }}
+  pre {{ background: #f6f8fa; padding: 1em; overflow-x: auto; }}
+  .kw {{ color: #0550ae; font-weight: bold; }}
+  .str {{ color: #0a3069; }}
+  .num {{ color: #953800; }}
+  table {{ border-collapse: collapse; margin: 1em 0; }}
+  th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}
+  th {{ background: #f0f0f0; }}
+  .note {{ color: #666; font-size: 0.9em; }}
+  section {{ margin-bottom: 3em; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<p>{database_label}: <code>{database}</code></p>
+{body}</body>
+</html>
+"#,
+        lang = t!("report.lang"),
+        title = escape_markup(t!("report.title")),
+        database_label = escape_markup(t!("report.database")),
+        database = escape_markup(database),
+        body = body
+    )
+}
+
+/// Columnas y primeras filas del resultado como texto
+fn table(result: &Value) -> (Vec<String>, Vec<Vec<String>>) {
+    let Some(rows) = result["rows"].as_array() else {
+        return (Vec::new(), Vec::new());
+    };
+    let Some(first) = rows.first().and_then(|row| row.as_object()) else {
+        return (Vec::new(), Vec::new());
+    };
+
+    let columns: Vec<String> = first.keys().cloned().collect();
+    let rows = rows
+        .iter()
+        .take(MAX_ROWS)
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| value_to_string(&row[column]))
+                .collect()
+        })
+        .collect();
+
+    (columns, rows)
+}
+
+fn truncation_note(result: &Value) -> Option<String> {
+    let count = result["count"].as_u64().unwrap_or(0) as usize;
+    (count > MAX_ROWS).then(|| t!("report.truncated", shown = MAX_ROWS, total = count))
+}
+
+/// Resaltado de palabras clave, textos y números con clases CSS
+fn highlight_sql(sql: &str) -> String {
+    let Ok(token) = Regex::new(&format!(
+        r"'(?:[^']|'')*'|\b\d+(?:\.\d+)?\b|{}",
+        SQL_KEYWORDS
+    )) else {
+        return escape_markup(sql);
+    };
+
+    let mut output = String::new();
+    let mut last = 0;
+    for found in token.find_iter(sql) {
+        output.push_str(&escape_markup(&sql[last..found.start()]));
+        let text = found.as_str();
+        let class = if text.starts_with('\'') {
+            "str"
+        } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+            "num"
+        } else {
+            "kw"
+        };
+        output.push_str(&format!(
+            "<span class=\"{}\">{}</span>",
+            class,
+            escape_markup(text)
+        ));
+        last = found.end();
+    }
+    output.push_str(&escape_markup(&sql[last..]));
+    output
+}
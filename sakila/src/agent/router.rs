@@ -0,0 +1,213 @@
+use serde::Serialize;
+
+use super::examples::normalize;
+use crate::i18n::{Locale, locale};
+
+/// Camino que sigue un mensaje del usuario
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum Route {
+    /// Pregunta que necesita consultar datos: bucle SQL
+    Data,
+    /// Pregunta sobre tablas y columnas: se responde con el esquema
+    Schema,
+    /// Pregunta sobre los resultados anteriores: bucle SQL con la query previa
+    FollowUp,
+    /// Saludos, agradecimientos...: respuesta directa sin SQL
+    SmallTalk,
+}
+
+/// Cómo se decidió la ruta
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum RouteMethod {
+    Rules,
+    Model,
+    Default,
+}
+
+/// Etiquetas que el modelo puede elegir, en el mismo orden que ROUTES
+const LABELS_ES: [&str; 4] = ["datos", "esquema", "seguimiento", "charla"];
+const LABELS_EN: [&str; 4] = ["data", "schema", "follow-up", "chat"];
+pub(super) const ROUTES: [Route; 4] = [
+    Route::Data,
+    Route::Schema,
+    Route::FollowUp,
+    Route::SmallTalk,
+];
+
+const SMALL_TALK: [&str; 17] = [
+    "hola",
+    "que tal",
+    "buenas",
+    "buenos dias",
+    "buenas tardes",
+    "gracias",
+    "adios",
+    "hasta luego",
+    "que puedes hacer",
+    "quien eres",
+    "ayuda",
+    "hello",
+    "hi",
+    "thanks",
+    "thank you",
+    "bye",
+    "what can you do",
+];
+
+/// Frases que piden la estructura; "tabla" o "columna" solas no bastan
+/// ("lista los clientes de la tabla customer" pide datos)
+const SCHEMA: [&str; 17] = [
+    "que tablas",
+    "que columnas",
+    "que campos",
+    "columnas tiene",
+    "campos tiene",
+    "estructura de",
+    "esquema de",
+    "describe",
+    "describeme",
+    "what tables",
+    "which tables",
+    "what columns",
+    "which columns",
+    "columns does",
+    "fields does",
+    "structure of",
+    "schema of",
+];
+
+/// Palabras que indican que se piden datos, no la estructura
+const DATA_HINTS: [&str; 12] = [
+    "cuantas",
+    "cuantos",
+    "cuanto",
+    "filas",
+    "registros",
+    "total",
+    "promedio",
+    "media",
+    "suma",
+    "top",
+    "count",
+    "rows",
+];
+
+const FOLLOW_UP: [&str; 10] = [
+    "de esos",
+    "de esas",
+    "de ellos",
+    "de ellas",
+    "de estos",
+    "anterior",
+    "los mismos",
+    "ordenalos",
+    "those",
+    "previous",
+];
+
+/// Etiquetas en el idioma del prompt de clasificación
+pub(super) fn labels() -> [&'static str; 4] {
+    match locale() {
+        Locale::Es => LABELS_ES,
+        Locale::En => LABELS_EN,
+    }
+}
+
+/// Reglas rápidas para los casos claros; `None` si no deciden
+pub(super) fn by_rules(question: &str, has_previous: bool) -> Option<Route> {
+    let words = normalize(question);
+    let text = format!(" {} ", words.join(" "));
+    let contains = |phrase: &str| text.contains(&format!(" {} ", phrase));
+
+    // Mensajes que solo son un saludo o un agradecimiento ("muchas gracias")
+    if SMALL_TALK.iter().any(|phrase| contains(phrase)) {
+        let rest = SMALL_TALK.iter().fold(text.clone(), |rest, phrase| {
+            rest.replace(&format!(" {} ", phrase), " ")
+        });
+        if rest.split_whitespace().count() <= 1 {
+            return Some(Route::SmallTalk);
+        }
+    }
+
+    if has_previous
+        && (words
+            .first()
+            .is_some_and(|word| word == "y" || word == "and")
+            || FOLLOW_UP.iter().any(|phrase| contains(phrase)))
+    {
+        return Some(Route::FollowUp);
+    }
+
+    if SCHEMA.iter().any(|phrase| contains(phrase)) && !DATA_HINTS.iter().any(|word| contains(word))
+    {
+        return Some(Route::Schema);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_talk() {
+        assert_eq!(by_rules("¡Hola!", false), Some(Route::SmallTalk));
+        assert_eq!(by_rules("Muchas gracias", true), Some(Route::SmallTalk));
+        assert_eq!(by_rules("What can you do?", false), Some(Route::SmallTalk));
+        assert_eq!(by_rules("Hola, ¿cuántos clientes hay?", false), None);
+    }
+
+    #[test]
+    fn follow_up_needs_a_previous_query() {
+        assert_eq!(
+            by_rules("¿Y de esos, cuántos son de Canadá?", true),
+            Some(Route::FollowUp)
+        );
+        assert_eq!(
+            by_rules("ordénalos por apellido", true),
+            Some(Route::FollowUp)
+        );
+        assert_eq!(by_rules("¿Y de esos, cuántos son de Canadá?", false), None);
+    }
+
+    #[test]
+    fn schema_questions() {
+        assert_eq!(
+            by_rules("¿Qué columnas tiene la tabla film?", false),
+            Some(Route::Schema)
+        );
+        assert_eq!(
+            by_rules("Describe la tabla customer", false),
+            Some(Route::Schema)
+        );
+        assert_eq!(
+            by_rules("¿Cuál es la estructura de la tabla rental?", false),
+            Some(Route::Schema)
+        );
+        assert_eq!(
+            by_rules("Which tables are there?", false),
+            Some(Route::Schema)
+        );
+    }
+
+    #[test]
+    fn tables_in_data_questions() {
+        assert_eq!(
+            by_rules("lista los clientes de la tabla customer", false),
+            None
+        );
+        assert_eq!(by_rules("Show the rows of the payment table", false), None);
+        assert_eq!(
+            by_rules("¿Cuántas filas tiene la tabla rental?", false),
+            None
+        );
+        assert_eq!(
+            by_rules("¿Qué columnas tienen más registros nulos?", false),
+            None
+        );
+        assert_eq!(by_rules("Top 10 películas más alquiladas", false), None);
+    }
+}
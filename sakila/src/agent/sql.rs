@@ -1,7 +1,9 @@
 use color_eyre::{Result, eyre::eyre};
 use regex::Regex;
 use serde_json::{Value, json};
-use sqlx::{Column, Pool, Row, Sqlite};
+
+use super::digest;
+use crate::{config::ResultsConfig, db::Database, t};
 
 pub(super) fn extract_sql(text: &str) -> Option<String> {
     let re = Regex::new(r"(?s)<sql>(.*?)</sql>").ok()?;
@@ -10,81 +12,61 @@ pub(super) fn extract_sql(text: &str) -> Option<String> {
         .map(|m| m.as_str().trim().to_string())
 }
 
-pub(super) async fn run_query(pool: &Pool<Sqlite>, query: &str) -> Result<Value> {
+/// Motivo por el que una query no se puede ejecutar, si lo hay
+pub(super) fn check_query(query: &str) -> Option<&'static str> {
     let query_upper = query.trim().to_uppercase();
     if query_upper.starts_with("DROP")
         || query_upper.starts_with("DELETE")
         || query_upper.starts_with("UPDATE")
         || query_upper.starts_with("INSERT")
     {
-        return Err(eyre!("Query no permitida: solo SELECT"));
+        return Some(t!("agent.query_not_allowed"));
     }
+    None
+}
 
-    // Ejecutar
-    let rows = sqlx::query(query).fetch_all(pool).await?;
-
-    if rows.is_empty() {
-        return Ok(json!({"rows": [], "count": 0}));
+pub(super) async fn run_query(db: &Database, query: &str) -> Result<Value> {
+    if let Some(reason) = check_query(query) {
+        return Err(eyre!(reason));
     }
 
-    let mut results = Vec::new();
-    for row in &rows {
-        let mut obj = serde_json::Map::new();
-
-        for (i, column) in row.columns().iter().enumerate() {
-            let column_name = column.name();
-
-            // Intentar tipos en orden (de más específico a más genérico)
-            let value =
-                // Intentar i32 primero (SMALLINT en SQLite)
-                if let Ok(Some(v)) = row.try_get::<Option<i32>, _>(i) {
-                    json!(v)
-                }
-                // Intentar i64 (INTEGER, BIGINT)
-                else if let Ok(Some(v)) = row.try_get::<Option<i64>, _>(i) {
-                    json!(v)
-                }
-                // Intentar u32 (por si acaso)
-                else if let Ok(Some(v)) = row.try_get::<Option<u32>, _>(i) {
-                    json!(v)
-                }
-                // Intentar f64 (REAL, FLOAT, DOUBLE)
-                else if let Ok(Some(v)) = row.try_get::<Option<f64>, _>(i) {
-                    json!(v)
-                }
-                // Intentar String (TEXT, VARCHAR, CHAR, TIMESTAMP)
-                else if let Ok(Some(v)) = row.try_get::<Option<String>, _>(i) {
-                    json!(v)
-                }
-                // Intentar bool
-                else if let Ok(Some(v)) = row.try_get::<Option<bool>, _>(i) {
-                    json!(v)
-                }
-                // Si todo falla o es NULL explícito
-                else {
-                    json!(null)
-                };
-
-            obj.insert(column_name.to_string(), value);
-        }
-
-        results.push(Value::Object(obj));
-    }
+    // Ejecutar
+    let results = db.query(query).await?;
 
     Ok(json!({
+        "count": results.len(),
         "rows": results,
-        "count": results.len()
     }))
 }
 
-pub(super) fn format_results(result: &Value) -> String {
+/// Texto del resultado para el modelo, dentro de `token_budget` tanto en
+/// formato tabla como en resumen
+pub(super) fn format_results(
+    result: &Value,
+    cfg: &ResultsConfig,
+    count_tokens: &dyn Fn(&str) -> usize,
+) -> String {
     let count = result["count"].as_u64().unwrap_or(0);
 
     if count == 0 {
-        return "No se encontraron resultados.".to_string();
+        return t!("results.empty").to_string();
     }
 
-    let rows = result["rows"].as_array().unwrap();
+    let rows = result["rows"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    digest::fit(rows, cfg.token_budget, count_tokens, &|rows| {
+        render_rows(rows, cfg, count_tokens)
+    })
+}
+
+fn render_rows(
+    rows: &[Value],
+    cfg: &ResultsConfig,
+    count_tokens: &dyn Fn(&str) -> usize,
+) -> String {
+    let count = rows.len();
 
     // Si es una sola fila con un solo valor, output simple
     if count == 1 {
@@ -92,38 +74,94 @@ pub(super) fn format_results(result: &Value) -> String {
         if let Some(obj) = row.as_object() {
             if obj.len() == 1 {
                 let value = obj.values().next().unwrap();
-                return format!("Resultado: {}", value);
+                return t!("results.single", value = value);
             }
         }
     }
 
+    // Resultados grandes: resumen con estadísticas en lugar de truncar
+    if rows.len() > cfg.preview_rows {
+        return digest::summarize(rows, cfg, count_tokens);
+    }
+
     // Sino, formato tabla
-    let mut output = format!("Resultados ({} filas):\n", count);
+    let mut output = format!("{}\n", t!("results.table", count = count));
 
-    for (i, row) in rows.iter().take(10).enumerate() {
-        output.push_str(&format!("{}. ", i + 1));
-        if let Some(obj) = row.as_object() {
-            let values: Vec<String> = obj
-                .iter()
-                .map(|(k, v)| {
-                    let value_str = match v {
-                        Value::Null => "NULL".to_string(),
-                        Value::Number(n) => n.to_string(),
-                        Value::String(s) => s.clone(),
-                        Value::Bool(b) => b.to_string(),
-                        _ => v.to_string(),
-                    };
-                    format!("{}: {}", k, value_str)
-                })
-                .collect();
-            output.push_str(&values.join(", "));
+    for (i, row) in rows.iter().enumerate() {
+        output.push_str(&digest::format_row(i + 1, row));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cfg(token_budget: usize) -> ResultsConfig {
+        ResultsConfig {
+            preview_rows: 10,
+            edge_rows: 5,
+            top_values: 3,
+            token_budget,
         }
-        output.push('\n');
     }
 
-    if count > 10 {
-        output.push_str(&format!("... y {} filas más\n", count - 10));
+    fn count_tokens(text: &str) -> usize {
+        text.chars().count() / 4
     }
 
-    output
+    fn result(rows: Vec<Value>) -> Value {
+        json!({ "count": rows.len(), "rows": rows })
+    }
+
+    #[test]
+    fn table_fits_by_trimming_cells() {
+        let rows = (0..5)
+            .map(|i| json!({ "id": i, "description": "x".repeat(2000) }))
+            .collect();
+
+        let text = format_results(&result(rows), &cfg(300), &count_tokens);
+        assert!(count_tokens(&text) <= 300, "{}", text);
+        assert!(text.contains('…'));
+        assert!(text.contains("description"));
+    }
+
+    #[test]
+    fn table_fits_by_dropping_columns() {
+        let rows = (0..10)
+            .map(|i| {
+                let obj: serde_json::Map<String, Value> = (0..40)
+                    .map(|c| (format!("column_{:02}", c), json!(i * c)))
+                    .collect();
+                Value::Object(obj)
+            })
+            .collect();
+
+        let text = format_results(&result(rows), &cfg(400), &count_tokens);
+        assert!(count_tokens(&text) <= 400, "{}", text);
+        assert!(text.contains("column_00"));
+        assert!(text.contains("column_39"));
+        assert!(!text.contains("column_39:"));
+    }
+
+    #[test]
+    fn summary_fits_by_trimming_cells() {
+        let rows = (0..200)
+            .map(|i| json!({ "id": i, "notes": format!("{}{}", i, "y".repeat(3000)) }))
+            .collect();
+
+        let text = format_results(&result(rows), &cfg(400), &count_tokens);
+        assert!(count_tokens(&text) <= 400, "{}", text);
+        assert!(text.contains('…'));
+    }
+
+    #[test]
+    fn small_results_are_unchanged() {
+        let rows = vec![json!({ "title": "ACADEMY DINOSAUR", "length": 86 })];
+
+        let text = format_results(&result(rows), &cfg(800), &count_tokens);
+        assert!(text.contains("ACADEMY DINOSAUR"));
+        assert!(!text.contains('…'));
+    }
 }
@@ -0,0 +1,272 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use regex::Regex;
+
+use super::examples::normalize;
+
+/// Palabras que no son entidades de la base de datos
+const IGNORED_WORDS: [&str; 9] = [
+    "SQL", "SELECT", "FROM", "WHERE", "NULL", "ID", "USD", "OK", "SAKILA",
+];
+
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+(?:[.,]\d+)*").expect("NUMBER"));
+static SQL_BLOCK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<sql>.*?</sql>").expect("SQL_BLOCK"));
+static SQL_STRING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"'((?:[^']|'')*)'").expect("SQL_STRING"));
+static LIST_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*\d+[.)]\s+").expect("LIST_MARKER"));
+/// Nombres en mayúsculas ("PENELOPE GUINESS") o con mayúscula inicial
+/// ("Mary Smith", "Canada")
+static ENTITY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b(?:[A-ZÁÉÍÓÚÑ]{2,}(?:[ \-][A-ZÁÉÍÓÚÑ]{2,})*|[A-ZÁÉÍÓÚÑ][a-záéíóúñü]+(?:[ \-][A-ZÁÉÍÓÚÑ][a-záéíóúñü]+)*)\b",
+    )
+    .expect("ENTITY")
+});
+
+/// Resultado de una query tal como lo vio el modelo
+pub(super) struct Observed {
+    pub sql: String,
+    /// Texto enviado al modelo: tabla o resumen con estadísticas
+    pub rendered: String,
+}
+
+/// Valores que el modelo ha visto durante el turno
+struct Evidence {
+    numbers: Vec<f64>,
+    words: HashSet<String>,
+}
+
+impl Evidence {
+    fn new(question: &str, observed: &[Observed]) -> Self {
+        let mut evidence = Self {
+            numbers: Vec::new(),
+            words: HashSet::new(),
+        };
+
+        evidence.add_text(question);
+        for result in observed {
+            evidence.add_text(&result.rendered);
+            evidence.add_literals(&result.sql);
+        }
+
+        evidence
+    }
+
+    fn add_text(&mut self, text: &str) {
+        for claim in NUMBER.find_iter(text) {
+            self.numbers.extend(parse_number(claim.as_str()));
+        }
+        self.words.extend(normalize(text));
+    }
+
+    /// Cadenas y números de la query (`WHERE year = '2005'`), pero no los
+    /// nombres de tablas y columnas
+    fn add_literals(&mut self, sql: &str) {
+        for literal in SQL_STRING.captures_iter(sql) {
+            self.add_text(&literal[1]);
+        }
+        let code = SQL_STRING.replace_all(sql, " ");
+        for claim in NUMBER.find_iter(&code) {
+            self.numbers.extend(parse_number(claim.as_str()));
+        }
+    }
+
+    fn supports_number(&self, claim: &str) -> bool {
+        let candidates = parse_number(claim);
+        self.numbers.iter().any(|known| {
+            candidates.iter().any(|value| {
+                let tolerance = (known.abs() * 0.005).max(0.01);
+                (known - value).abs() <= tolerance
+            })
+        })
+    }
+
+    /// Sin distinguir mayúsculas ni acentos ("Canadá" y "CANADA")
+    fn supports_entity(&self, entity: &str) -> bool {
+        normalize(entity)
+            .iter()
+            .all(|word| self.words.contains(word))
+    }
+}
+
+/// Interpretaciones posibles de un número escrito en la respuesta
+/// ("1,000.50" en inglés o "1.000,50" en español)
+fn parse_number(text: &str) -> Vec<f64> {
+    let english = text.replace(',', "");
+    let spanish = text.replace('.', "").replace(',', ".");
+    [english, spanish, text.to_string()]
+        .iter()
+        .filter_map(|candidate| candidate.parse::<f64>().ok())
+        .collect()
+}
+
+/// Devuelve los datos mencionados en la respuesta final que no aparecen en
+/// la pregunta ni en ninguno de los resultados que vio el modelo durante el
+/// turno.
+pub(super) fn unsupported_claims(
+    answer: &str,
+    question: &str,
+    observed: &[Observed],
+) -> Vec<String> {
+    let evidence = Evidence::new(question, observed);
+
+    // Las queries y los marcadores de lista no son afirmaciones sobre los datos
+    let text = SQL_BLOCK.replace_all(answer, " ");
+    let text = LIST_MARKER.replace_all(&text, "");
+
+    let mut unsupported: Vec<String> = Vec::new();
+
+    for claim in NUMBER.find_iter(&text) {
+        let claim = claim.as_str();
+        if !evidence.supports_number(claim) && !unsupported.iter().any(|c| c == claim) {
+            unsupported.push(claim.to_string());
+        }
+    }
+
+    for entity in ENTITY.find_iter(&text) {
+        let mut name = entity.as_str();
+        // Al empezar una frase la mayúscula no indica un nombre ("Hay",
+        // "En Canada"): esa primera palabra no se comprueba
+        if starts_sentence(&text[..entity.start()]) && !is_upper(name) {
+            match name.split_once([' ', '-']) {
+                Some((_, rest)) => name = rest,
+                None => continue,
+            }
+        }
+        if IGNORED_WORDS.contains(&name.to_uppercase().as_str()) {
+            continue;
+        }
+        if !evidence.supports_entity(name) && !unsupported.iter().any(|c| c == name) {
+            unsupported.push(name.to_string());
+        }
+    }
+
+    unsupported
+}
+
+/// Lo anterior al texto termina una frase (o no hay nada)
+fn starts_sentence(before: &str) -> bool {
+    before
+        .trim_end()
+        .chars()
+        .last()
+        .is_none_or(|c| !c.is_alphanumeric() && c != ',' && c != ';')
+}
+
+fn is_upper(text: &str) -> bool {
+    !text.chars().any(char::is_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observed(sql: &str, rendered: &str) -> Vec<Observed> {
+        vec![Observed {
+            sql: sql.to_string(),
+            rendered: rendered.to_string(),
+        }]
+    }
+
+    #[test]
+    fn thousands_and_decimal_separators() {
+        let results = observed(
+            "SELECT SUM(amount) AS total FROM payment",
+            "Resultado: 67416.51",
+        );
+
+        assert!(unsupported_claims("El total es 67,416.51", "", &results).is_empty());
+        assert!(unsupported_claims("El total es 67.416,51", "", &results).is_empty());
+        assert!(unsupported_claims("El total es 67416.51", "", &results).is_empty());
+        assert_eq!(
+            unsupported_claims("El total es 71.000,00", "", &results),
+            vec!["71.000,00"]
+        );
+    }
+
+    #[test]
+    fn rounded_numbers_are_supported() {
+        let results = observed("SELECT AVG(rental_rate) FROM film", "Resultado: 2.980000");
+
+        assert!(unsupported_claims("La media es 2.98", "", &results).is_empty());
+        assert_eq!(
+            unsupported_claims("La media es 3.5", "", &results),
+            vec!["3.5"]
+        );
+    }
+
+    #[test]
+    fn sql_literals_are_evidence() {
+        let results = observed(
+            "SELECT COUNT(*) FROM rental WHERE strftime('%Y', rental_date) = '2005'",
+            "Resultado: 15862",
+        );
+
+        let claims = unsupported_claims("En 2005 hubo 15.862 alquileres", "", &results);
+        assert!(claims.is_empty(), "{:?}", claims);
+    }
+
+    #[test]
+    fn identifiers_in_sql_are_not_evidence() {
+        let results = observed("SELECT first_name FROM actor", "Resultado: PENELOPE");
+
+        assert!(unsupported_claims("El actor es PENELOPE", "", &results).is_empty());
+        assert_eq!(
+            unsupported_claims("El ACTOR es NICK", "", &results),
+            vec!["ACTOR", "NICK"]
+        );
+    }
+
+    #[test]
+    fn summary_figures_are_evidence() {
+        let results = observed(
+            "SELECT length FROM film",
+            "1000 filas. length: min 46, max 185, media 115.27",
+        );
+
+        let claims = unsupported_claims(
+            "Duran entre 46 y 185 minutos (115,27 de media)",
+            "",
+            &results,
+        );
+        assert!(claims.is_empty(), "{:?}", claims);
+    }
+
+    #[test]
+    fn title_case_names_are_checked() {
+        let results = observed(
+            "SELECT c.first_name, c.last_name, co.country FROM customer c ...",
+            "1. first_name: MARY, last_name: SMITH, country: Canada",
+        );
+
+        let claims = unsupported_claims("La clienta es Mary Smith, de Canadá.", "", &results);
+        assert!(claims.is_empty(), "{:?}", claims);
+        assert_eq!(
+            unsupported_claims("La clienta es John Doe, de Narnia.", "", &results),
+            vec!["John Doe", "Narnia"]
+        );
+    }
+
+    #[test]
+    fn sentence_starts_are_not_names() {
+        let results = observed("SELECT country FROM country", "Resultado: Canada");
+
+        let answer = "Hay un país. En Canada hay clientes.\n- Todos activos";
+        let claims = unsupported_claims(answer, "", &results);
+        assert!(claims.is_empty(), "{:?}", claims);
+        assert_eq!(
+            unsupported_claims("Mary Smith vive en Canada", "", &results),
+            vec!["Smith"]
+        );
+    }
+
+    #[test]
+    fn list_markers_and_queries_are_ignored() {
+        let results = observed("SELECT name FROM category", "Resultado: SPORTS");
+
+        let answer = "<sql>SELECT 42</sql>\n1. SPORTS\n2) SPORTS";
+        assert!(unsupported_claims(answer, "", &results).is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use tokio::sync::Notify;
+
+use crate::t;
+
+struct State {
+    cancelled: AtomicBool,
+    active: AtomicBool,
+    exit_armed: AtomicBool,
+    notify: Notify,
+}
+
+/// Cancelación cooperativa de la operación en curso (generación o query).
+/// Ctrl-C cancela la operación activa; sin operación activa, un segundo
+/// Ctrl-C seguido termina el programa.
+#[derive(Clone)]
+pub struct CancelToken(Arc<State>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self(Arc::new(State {
+            cancelled: AtomicBool::new(false),
+            active: AtomicBool::new(false),
+            exit_armed: AtomicBool::new(false),
+            notify: Notify::new(),
+        }))
+    }
+
+    /// Marca el inicio de una operación cancelable
+    pub fn start(&self) {
+        self.0.cancelled.store(false, Ordering::SeqCst);
+        self.0.active.store(true, Ordering::SeqCst);
+    }
+
+    pub fn finish(&self) {
+        self.0.active.store(false, Ordering::SeqCst);
+    }
+
+    /// El usuario escribió algo: el siguiente Ctrl-C vuelve a pedir confirmación
+    pub fn disarm(&self) {
+        self.0.exit_armed.store(false, Ordering::SeqCst);
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Se completa cuando se cancela la operación
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.0.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Atiende Ctrl-C en segundo plano durante toda la sesión
+    pub fn listen_ctrl_c(&self) {
+        let token = self.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if token.0.active.load(Ordering::SeqCst) {
+                    token.cancel();
+                } else if token.0.exit_armed.swap(true, Ordering::SeqCst) {
+                    println!("\n{}", t!("repl.bye"));
+                    std::process::exit(0);
+                } else {
+                    println!("\n{}", t!("repl.exit_hint"));
+                }
+            }
+        });
+    }
+}
@@ -2,25 +2,173 @@ use color_eyre::Result;
 use config::{Config, File, FileFormat};
 use serde::Deserialize;
 
+use crate::i18n::{Locale, Localized};
+
 #[derive(Debug, Deserialize)]
 pub struct AppConfig {
+    /// Idioma (es | en); SAKILA_LOCALE tiene prioridad y sin ninguno se usa LANG
+    #[serde(default)]
+    pub locale: Option<Locale>,
     pub db: DbConfig,
     pub agent: AgentConfig,
     pub tokenizer: TokenizerConfig,
     pub llm: LlmConfig,
     pub inference: InferenceConfig,
+    pub telemetry: TelemetryConfig,
+    pub tui: TuiConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct DbConfig {
     pub url: String,
     pub file: String,
+    /// SHA-256 esperado del fichero descargado
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Construir la base de datos con `scripts` en lugar de descargarla
+    #[serde(default)]
+    pub offline: bool,
+    /// Scripts SQL de esquema y datos, en orden. También se usan si falla la descarga
+    #[serde(default)]
+    pub scripts: Vec<String>,
+    /// Fichero SQLite, CSV/JSON o directorio de CSV/JSON. Si se indica,
+    /// se usa en lugar de Sakila (también como primer argumento del programa)
+    #[serde(default)]
+    pub dataset: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct AgentConfig {
-    pub system_prompt: String,
+    pub system_prompt: Localized<String>,
     pub max_iterations: usize,
+    pub results: ResultsConfig,
+    pub verification: VerificationConfig,
+    pub examples: ExamplesConfig,
+    pub feedback: FeedbackConfig,
+    pub audit: AuditConfig,
+    pub thinking_display: ThinkingDisplay,
+    pub router: RouterConfig,
+    pub plan: PlanConfig,
+    pub digests: DigestConfig,
+    pub cache: CacheConfig,
+    pub debug: DebugConfig,
+    pub clarify: ClarifyConfig,
+    pub pipeline: PipelineConfig,
+    pub injection: InjectionConfig,
+}
+
+/// Defensas frente a instrucciones dentro de los resultados. Los datos se
+/// escapan siempre; esto configura el resto
+#[derive(Debug, Deserialize, Clone)]
+pub struct InjectionConfig {
+    /// Delimitadores aleatorios alrededor de los datos de cada resultado
+    pub random_delimiters: bool,
+    /// Busca valores con aspecto de instrucciones antes de pasarlos al modelo
+    pub detect: bool,
+    pub mode: InjectionMode,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InjectionMode {
+    /// Avisa al usuario y al modelo
+    Warn,
+    /// Además sustituye los valores sospechosos antes de pasarlos al modelo
+    Redact,
+}
+
+/// Escritor de SQL y escritor de respuestas con prompts separados
+#[derive(Debug, Deserialize, Clone)]
+pub struct PipelineConfig {
+    pub enabled: bool,
+    /// Plantilla del escritor de SQL (mismas variables que system_prompt)
+    pub sql_prompt: Localized<String>,
+    /// Plantilla del escritor de respuestas, sin esquema
+    pub answer_prompt: Localized<String>,
+    #[serde(default)]
+    pub sql_sampling: SamplingOverride,
+    #[serde(default)]
+    pub answer_sampling: SamplingOverride,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ClarifyConfig {
+    pub enabled: bool,
+    /// Aclaraciones por pregunta; después el modelo tiene que responder
+    pub max_rounds: usize,
+    /// Instrucciones que se añaden al system prompt
+    pub prompt: Localized<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    /// Fichero JSONL con los resultados guardados
+    pub file: String,
+    /// Segundos que un resultado sigue siendo válido
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    /// Tamaño máximo de la caché (en bytes de JSON)
+    pub max_bytes: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DigestConfig {
+    /// Filas del resultado que se guardan en cada resumen
+    pub rows: usize,
+    /// Resúmenes de turnos anteriores que se mantienen en memoria
+    pub max_turns: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PlanConfig {
+    pub enabled: bool,
+    /// Filas a partir de las cuales una lectura completa se avisa
+    pub large_table_rows: u64,
+    /// Coste estimado (filas leídas) por encima del cual la query no se ejecuta
+    #[serde(default)]
+    pub max_cost: Option<u64>,
+    pub mode: PlanMode,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanMode {
+    /// Solo avisa al usuario
+    Warn,
+    /// Además indica al modelo cómo corregir la query
+    Hint,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RouterConfig {
+    pub enabled: bool,
+    /// Si las reglas no deciden, el modelo clasifica el mensaje
+    pub classifier: bool,
+    /// Instrucciones de la clasificación (categorías: datos, esquema, seguimiento, charla)
+    pub prompt: Localized<String>,
+    /// Instrucciones para responder sin SQL (charla y preguntas de esquema)
+    pub direct_prompt: Localized<String>,
+}
+
+/// Cómo se muestra el razonamiento del modelo en la terminal
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThinkingDisplay {
+    Dimmed,
+    Hidden,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ResultsConfig {
+    /// Filas a partir de las cuales se envía un resumen en lugar de la tabla
+    pub preview_rows: usize,
+    /// Filas que se muestran al inicio y al final del resumen
+    pub edge_rows: usize,
+    /// Valores más frecuentes por columna
+    pub top_values: usize,
+    /// Máximo de tokens que puede ocupar el resultado en el contexto
+    pub token_budget: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,7 +180,14 @@ pub struct TokenizerConfig {
     pub user_template: String,
     pub assistant_template: String,
     pub start_completion: String,
+    /// Inicio de la respuesta en modo thinking (sin bloque <think> vacío)
+    pub thinking_start_completion: String,
+    pub think_start: String,
+    pub think_end: String,
     pub banned_tokens: Vec<String>,
+    /// Tokens que, además de eos_token, terminan la generación
+    #[serde(default)]
+    pub stop_tokens: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,6 +196,9 @@ pub struct LlmConfig {
     pub file: String,
     pub branch: String,
     pub seed: u64,
+    /// Ventana de contexto del modelo, para mostrar cuánto se usa
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub context_length: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,6 +207,100 @@ pub struct InferenceConfig {
     pub temperature: f64,
     pub top_p: f64,
     pub top_k: usize,
+    /// Textos que terminan la generación (se conservan en la respuesta)
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+    pub thinking: ThinkingConfig,
+}
+
+/// Parámetros de muestreo que sustituyen a los de `inference` en una petición
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+pub struct SamplingOverride {
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    #[serde(default)]
+    pub top_p: Option<f64>,
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    #[serde(default)]
+    pub max_length: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ThinkingConfig {
+    pub enabled: bool,
+    /// Máximo de tokens de razonamiento antes de forzar el cierre </think>
+    pub budget: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VerificationConfig {
+    pub enabled: bool,
+    pub mode: VerificationMode,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationMode {
+    /// Solo avisa al usuario de los datos sin respaldo
+    Warn,
+    /// Pide al modelo una corrección (una iteración extra)
+    Retry,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExamplesConfig {
+    /// Fichero JSONL con los pares (pregunta, SQL) guardados con /good
+    pub file: String,
+    /// Ejemplos parecidos que se añaden al contexto en cada pregunta
+    pub top_k: usize,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FeedbackConfig {
+    /// Fichero JSONL con las valoraciones (/good, /bad, /fix)
+    pub file: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    /// Directorio del log (audit.jsonl y ficheros rotados)
+    pub dir: String,
+    /// Tamaño a partir del cual se rota el fichero
+    pub max_bytes: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DebugConfig {
+    pub enabled: bool,
+    /// Directorio con una carpeta por sesión
+    pub dir: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TelemetryConfig {
+    pub format: TelemetryFormat,
+    /// Fichero de salida de logs y spans (por defecto stdout)
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Colector OTLP/HTTP, p. ej. http://localhost:4318/v1/traces (feature `otlp`)
+    #[serde(default)]
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TelemetryFormat {
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TuiConfig {
+    /// En la TUI los logs no pueden ir a la terminal: se escriben aquí
+    /// si telemetry.file no está definido
+    pub log_file: String,
 }
 
 impl AppConfig {
@@ -0,0 +1,114 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use color_eyre::{Result, eyre::eyre};
+use duckdb::{AccessMode, Config, Connection};
+use serde_json::Value;
+
+/// DuckDB es síncrono: la conexión se comparte entre hilos y cada
+/// query se ejecuta con `spawn_blocking`
+#[derive(Clone)]
+pub struct DuckDb(Arc<Mutex<Connection>>);
+
+impl DuckDb {
+    pub(super) fn open(path: &Path) -> Result<Self> {
+        let config = Config::default().access_mode(AccessMode::ReadOnly)?;
+        let connection = Connection::open_with_flags(path, config)?;
+        Ok(Self(Arc::new(Mutex::new(connection))))
+    }
+
+    /// Igual que en Postgres, cada fila llega como JSON (fechas, DECIMAL,
+    /// listas... ya convertidos)
+    pub(super) async fn query(&self, query: &str) -> Result<Vec<Value>> {
+        let connection = self.0.clone();
+        let query = query.trim().trim_end_matches(';');
+        let wrapped = format!("SELECT to_json(t)::VARCHAR FROM ({}) t", query);
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| eyre!("La conexión con DuckDB no está disponible"))?;
+            let mut statement = connection.prepare(&wrapped)?;
+            let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+
+            rows.map(|row| Ok(serde_json::from_str(&row?)?)).collect()
+        })
+        .await?
+    }
+
+    /// Plan físico en texto, una línea por fila del diagrama
+    pub(super) async fn explain(&self, query: &str) -> Result<Vec<String>> {
+        let connection = self.0.clone();
+        let query = format!("EXPLAIN {}", query.trim().trim_end_matches(';'));
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| eyre!("La conexión con DuckDB no está disponible"))?;
+            let mut statement = connection.prepare(&query)?;
+            let plans = statement.query_map([], |row| row.get::<_, String>(1))?;
+
+            let mut lines = Vec::new();
+            for plan in plans {
+                lines.extend(plan?.lines().map(str::to_string));
+            }
+            Ok(lines)
+        })
+        .await?
+    }
+
+    /// (tabla, columna, tipo) de las tablas de usuario
+    pub(super) async fn columns(&self) -> Result<Vec<(String, String, String)>> {
+        let rows = self
+            .query(
+                "SELECT table_name, column_name, data_type
+                 FROM information_schema.columns
+                 WHERE table_schema = 'main'
+                 ORDER BY table_name, ordinal_position",
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let text = |key: &str| row[key].as_str().unwrap_or_default().to_string();
+                (text("table_name"), text("column_name"), text("data_type"))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn opens_read_only() {
+        let path = std::env::temp_dir().join(format!("sakila-test-{}.duckdb", std::process::id()));
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE actor (id INTEGER); INSERT INTO actor VALUES (1);")
+            .unwrap();
+
+        let db = DuckDb::open(&path).unwrap();
+        assert_eq!(db.query("SELECT id FROM actor").await.unwrap().len(), 1);
+        {
+            let connection = db.0.lock().unwrap();
+            assert!(
+                connection
+                    .execute_batch("INSERT INTO actor VALUES (2)")
+                    .is_err()
+            );
+            assert!(
+                connection
+                    .execute_batch("CREATE TABLE film (id INTEGER)")
+                    .is_err()
+            );
+        }
+
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,391 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::{Result, eyre::eyre};
+use serde_json::Value;
+use sqlx::{
+    Pool, Sqlite,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
+
+use crate::t;
+
+const EXTENSIONS: [&str; 3] = ["csv", "json", "jsonl"];
+
+/// Tabla leída de un fichero, antes de crearla en SQLite
+struct Table {
+    name: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+}
+
+pub(super) fn is_importable(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Ficheros CSV/JSON del directorio, en orden alfabético
+pub(super) fn dataset_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_importable(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Importa los ficheros en una base de datos SQLite en memoria: una tabla
+/// por fichero, con los tipos de columna inferidos de los valores
+pub(super) async fn import(files: &[PathBuf]) -> Result<Pool<Sqlite>> {
+    // Una sola conexión que no caduca: la base de datos vive con ella
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect_with(SqliteConnectOptions::new().in_memory(true))
+        .await?;
+
+    let mut names: Vec<String> = Vec::new();
+    for file in files {
+        let mut table = read_table(file)?;
+        table.name = unique_name(&table.name, &names);
+        names.push(table.name.clone());
+
+        create_table(&pool, &table).await?;
+        tracing::info!(
+            "📥 {} importado como tabla {} ({} filas)",
+            file.display(),
+            table.name,
+            table.rows.len()
+        );
+    }
+
+    // La conexión no caduca, así que el pragma dura lo que la base de datos
+    sqlx::query("PRAGMA query_only = ON").execute(&pool).await?;
+
+    Ok(pool)
+}
+
+fn read_table(path: &Path) -> Result<Table> {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| eyre!("{}", t!("db.invalid_file_name", path = path.display())))?;
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let (columns, rows) = match extension.as_str() {
+        "csv" => read_csv(path)?,
+        "json" => {
+            let content = std::fs::read_to_string(path)?;
+            match serde_json::from_str(&content)? {
+                Value::Array(records) => read_records(records)?,
+                _ => return Err(eyre!("{}", t!("db.not_an_array", path = path.display()))),
+            }
+        }
+        _ => {
+            let records = std::fs::read_to_string(path)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<Value>, _>>()?;
+            read_records(records)?
+        }
+    };
+
+    let mut unique_columns: Vec<String> = Vec::new();
+    for column in &columns {
+        let column = unique_name(&identifier(column), &unique_columns);
+        unique_columns.push(column);
+    }
+
+    Ok(Table {
+        name: identifier(stem),
+        columns: unique_columns,
+        rows,
+    })
+}
+
+fn read_csv(path: &Path) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let columns: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let row = (0..columns.len())
+            .map(|i| parse_cell(record.get(i).unwrap_or_default()))
+            .collect();
+        rows.push(row);
+    }
+
+    Ok((columns, rows))
+}
+
+/// Las columnas son la unión de las claves, en orden de aparición (dentro de
+/// cada objeto, serde_json las da en orden alfabético)
+fn read_records(records: Vec<Value>) -> Result<(Vec<String>, Vec<Vec<Value>>)> {
+    let mut columns: Vec<String> = Vec::new();
+    for record in &records {
+        let Value::Object(fields) = record else {
+            return Err(eyre!("{}", t!("db.not_an_object")));
+        };
+        for key in fields.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+
+    let rows = records
+        .iter()
+        .map(|record| {
+            columns
+                .iter()
+                .map(|column| match record.get(column) {
+                    // Objetos y arrays anidados se guardan como texto JSON
+                    Some(value @ (Value::Object(_) | Value::Array(_))) => {
+                        Value::String(value.to_string())
+                    }
+                    Some(value) => value.clone(),
+                    None => Value::Null,
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok((columns, rows))
+}
+
+/// Texto de CSV a valor: vacío es NULL y los números se reconocen,
+/// salvo los que tienen ceros a la izquierda (códigos postales, teléfonos...)
+fn parse_cell(text: &str) -> Value {
+    let text = text.trim();
+    if text.is_empty() {
+        return Value::Null;
+    }
+
+    let leading_zero = text.len() > 1 && text.starts_with('0') && !text.starts_with("0.");
+    if !leading_zero {
+        if let Ok(number) = text.parse::<i64>() {
+            return Value::from(number);
+        }
+        match text.parse::<f64>() {
+            Ok(number) if number.is_finite() => return Value::from(number),
+            _ => {}
+        }
+    }
+
+    Value::String(text.to_string())
+}
+
+/// INTEGER si todos los valores son enteros, REAL si son números y TEXT en otro caso
+fn column_type(rows: &[Vec<Value>], index: usize) -> &'static str {
+    let values = rows.iter().map(|row| &row[index]).filter(|v| !v.is_null());
+
+    let mut sql_type = "INTEGER";
+    for value in values {
+        match value {
+            Value::Number(n) if n.is_i64() || n.is_u64() => {}
+            Value::Bool(_) => {}
+            Value::Number(_) => sql_type = "REAL",
+            _ => return "TEXT",
+        }
+    }
+    sql_type
+}
+
+async fn create_table(pool: &Pool<Sqlite>, table: &Table) -> Result<()> {
+    let definitions: Vec<String> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("\"{}\" {}", column, column_type(&table.rows, i)))
+        .collect();
+    sqlx::query(&format!(
+        "CREATE TABLE \"{}\" ({})",
+        table.name,
+        definitions.join(", ")
+    ))
+    .execute(pool)
+    .await?;
+
+    let placeholders = vec!["?"; table.columns.len()].join(", ");
+    let insert = format!("INSERT INTO \"{}\" VALUES ({})", table.name, placeholders);
+
+    let mut transaction = pool.begin().await?;
+    for row in &table.rows {
+        let mut query = sqlx::query(&insert);
+        for value in row {
+            query = match value {
+                Value::Null => query.bind(None::<String>),
+                Value::Bool(b) => query.bind(*b as i64),
+                Value::Number(n) => match n.as_i64() {
+                    Some(i) => query.bind(i),
+                    None => query.bind(n.as_f64()),
+                },
+                Value::String(s) => query.bind(s.clone()),
+                other => query.bind(other.to_string()),
+            };
+        }
+        query.execute(&mut *transaction).await?;
+    }
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// Nombre válido como identificador SQL sin comillas
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert_str(0, "t_");
+    }
+    identifier
+}
+
+fn unique_name(name: &str, taken: &[String]) -> String {
+    let mut candidate = name.to_string();
+    let mut suffix = 2;
+    while taken.contains(&candidate) {
+        candidate = format!("{}_{}", name, suffix);
+        suffix += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::Row;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sakila-test-{}-{}", std::process::id(), name))
+    }
+
+    /// Columnas de la tabla con su tipo declarado
+    async fn table_info(pool: &Pool<Sqlite>, table: &str) -> Vec<(String, String)> {
+        sqlx::query(&format!("PRAGMA table_info(\"{}\")", table))
+            .fetch_all(pool)
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| (row.get("name"), row.get("type")))
+            .collect()
+    }
+
+    #[test]
+    fn csv_cells() {
+        assert_eq!(parse_cell(""), Value::Null);
+        assert_eq!(parse_cell("  "), Value::Null);
+        assert_eq!(parse_cell(" 42 "), json!(42));
+        assert_eq!(parse_cell("-7"), json!(-7));
+        assert_eq!(parse_cell("0"), json!(0));
+        assert_eq!(parse_cell("0.5"), json!(0.5));
+        assert_eq!(parse_cell("3.25"), json!(3.25));
+        assert_eq!(parse_cell("08001"), json!("08001"));
+        assert_eq!(parse_cell("inf"), json!("inf"));
+        assert_eq!(parse_cell("NaN"), json!("NaN"));
+        assert_eq!(parse_cell("hola"), json!("hola"));
+    }
+
+    #[test]
+    fn column_types() {
+        let rows = vec![
+            vec![json!(1), json!(1), json!("a"), Value::Null, json!(true)],
+            vec![json!(2), json!(2.5), json!(3), Value::Null, json!(false)],
+            vec![Value::Null, json!(3), json!(4), Value::Null, json!(1)],
+        ];
+        assert_eq!(column_type(&rows, 0), "INTEGER");
+        assert_eq!(column_type(&rows, 1), "REAL");
+        assert_eq!(column_type(&rows, 2), "TEXT");
+        assert_eq!(column_type(&rows, 3), "INTEGER");
+        assert_eq!(column_type(&rows, 4), "INTEGER");
+    }
+
+    #[test]
+    fn json_records() {
+        let (columns, rows) = read_records(vec![
+            json!({"id": 1, "tags": ["a", "b"]}),
+            json!({"id": 2, "extra": {"x": 1}}),
+        ])
+        .unwrap();
+        assert_eq!(columns, vec!["id", "tags", "extra"]);
+        assert_eq!(rows[0], vec![json!(1), json!(r#"["a","b"]"#), Value::Null]);
+        assert_eq!(rows[1], vec![json!(2), Value::Null, json!(r#"{"x":1}"#)]);
+
+        assert!(read_records(vec![json!([1, 2])]).is_err());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(identifier("Ventas 2024"), "ventas_2024");
+        assert_eq!(identifier("2024"), "t_2024");
+        assert_eq!(identifier(""), "t_");
+        let taken = vec!["ventas".to_string(), "ventas_2".to_string()];
+        assert_eq!(unique_name("ventas", &taken), "ventas_3");
+        assert_eq!(unique_name("clientes", &taken), "clientes");
+    }
+
+    #[tokio::test]
+    async fn import_infers_column_types() {
+        let csv = temp_path("ventas.csv");
+        std::fs::write(
+            &csv,
+            "id,Importe,Código Postal,Nota\n1,10.5,08001,\n2,3,28001,ok\n",
+        )
+        .unwrap();
+        let json = temp_path("Ventas.json");
+        std::fs::write(
+            &json,
+            r#"[{"id": 1, "activo": true}, {"id": 2, "activo": false, "datos": {"a": 1}}]"#,
+        )
+        .unwrap();
+
+        let pool = import(&[csv.clone(), json.clone()]).await.unwrap();
+        let csv_table = identifier(csv.file_stem().unwrap().to_str().unwrap());
+        let types = table_info(&pool, &csv_table).await;
+        assert_eq!(
+            types,
+            vec![
+                ("id".to_string(), "INTEGER".to_string()),
+                ("importe".to_string(), "REAL".to_string()),
+                ("código_postal".to_string(), "TEXT".to_string()),
+                ("nota".to_string(), "TEXT".to_string()),
+            ]
+        );
+
+        // Mismo nombre tras normalizar: la segunda tabla lleva sufijo
+        let types = table_info(&pool, &format!("{}_2", csv_table)).await;
+        assert_eq!(
+            types,
+            vec![
+                ("activo".to_string(), "INTEGER".to_string()),
+                ("id".to_string(), "INTEGER".to_string()),
+                ("datos".to_string(), "TEXT".to_string()),
+            ]
+        );
+
+        let zip: String = sqlx::query_scalar(&format!(
+            "SELECT \"código_postal\" FROM \"{}\" WHERE id = 1",
+            csv_table
+        ))
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(zip, "08001");
+
+        std::fs::remove_file(&csv).unwrap();
+        std::fs::remove_file(&json).unwrap();
+    }
+}
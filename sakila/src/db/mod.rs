@@ -0,0 +1,319 @@
+use std::path::Path;
+
+use crate::{config::DbConfig, t};
+use color_eyre::{Result, eyre::eyre};
+use serde_json::Value;
+use sqlx::{
+    Pool, Postgres, Sqlite, SqlitePool,
+    postgres::{PgConnectOptions, PgPoolOptions},
+    sqlite::SqliteConnectOptions,
+};
+
+#[cfg(feature = "duckdb")]
+mod duckdb;
+mod import;
+mod postgres;
+mod sakila;
+mod sqlite;
+
+/// Dialecto SQL de cada backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    #[cfg_attr(not(feature = "duckdb"), allow(dead_code))]
+    DuckDb,
+    Postgres,
+}
+
+impl Dialect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "SQLite",
+            Dialect::DuckDb => "DuckDB",
+            Dialect::Postgres => "PostgreSQL",
+        }
+    }
+
+    /// Diferencias con las que el modelo suele equivocarse
+    pub fn hints(&self) -> &'static str {
+        match self {
+            Dialect::Sqlite => t!("dialect.sqlite"),
+            Dialect::DuckDb => t!("dialect.duckdb"),
+            Dialect::Postgres => t!("dialect.postgres"),
+        }
+    }
+}
+
+/// Base de datos sobre la que trabaja el agente, con su backend
+#[derive(Clone)]
+pub enum Database {
+    Sqlite(Pool<Sqlite>),
+    Postgres(Pool<Postgres>),
+    #[cfg(feature = "duckdb")]
+    DuckDb(duckdb::DuckDb),
+}
+
+impl Database {
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            Database::Sqlite(_) => Dialect::Sqlite,
+            Database::Postgres(_) => Dialect::Postgres,
+            #[cfg(feature = "duckdb")]
+            Database::DuckDb(_) => Dialect::DuckDb,
+        }
+    }
+
+    /// Ejecuta la query y devuelve cada fila como un objeto JSON
+    pub async fn query(&self, query: &str) -> Result<Vec<Value>> {
+        match self {
+            Database::Sqlite(pool) => sqlite::query(pool, query).await,
+            Database::Postgres(pool) => postgres::query(pool, query).await,
+            #[cfg(feature = "duckdb")]
+            Database::DuckDb(db) => db.query(query).await,
+        }
+    }
+
+    /// Plan de ejecución de la query, en el formato de cada backend
+    pub async fn explain(&self, query: &str) -> Result<Vec<String>> {
+        match self {
+            Database::Sqlite(pool) => sqlite::explain(pool, query).await,
+            Database::Postgres(pool) => postgres::explain(pool, query).await,
+            #[cfg(feature = "duckdb")]
+            Database::DuckDb(db) => db.explain(query).await,
+        }
+    }
+
+    /// Tablas y columnas de la base de datos, una línea por tabla
+    pub async fn describe_schema(&self) -> Result<String> {
+        let columns = match self {
+            Database::Sqlite(pool) => sqlite::columns(pool).await?,
+            Database::Postgres(pool) => postgres::columns(pool).await?,
+            #[cfg(feature = "duckdb")]
+            Database::DuckDb(db) => db.columns().await?,
+        };
+
+        let mut tables: Vec<(String, Vec<String>)> = Vec::new();
+        for (table, column, column_type) in columns {
+            let column = format!("{} {}", column, column_type);
+            match tables.last_mut() {
+                Some((name, columns)) if *name == table => columns.push(column),
+                _ => tables.push((table, vec![column])),
+            }
+        }
+
+        Ok(tables
+            .iter()
+            .map(|(table, columns)| format!("{}({})", table, columns.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+pub async fn load(cfg: &DbConfig) -> Result<Database> {
+    match &cfg.dataset {
+        Some(dataset) if is_postgres_url(dataset) => {
+            tracing::info!("🐘 Conectando con PostgreSQL en solo lectura");
+            Ok(Database::Postgres(connect_postgres(dataset).await?))
+        }
+        Some(dataset) if is_duckdb_file(Path::new(dataset)) => load_duckdb(Path::new(dataset)),
+        Some(dataset) => Ok(Database::Sqlite(load_dataset(Path::new(dataset)).await?)),
+        None => Ok(Database::Sqlite(sakila::load(cfg).await?)),
+    }
+}
+
+/// Todas las transacciones de la sesión son de solo lectura
+/// (`-c default_transaction_read_only=on`), como los demás backends
+async fn connect_postgres(url: &str) -> Result<Pool<Postgres>> {
+    let options = url
+        .parse::<PgConnectOptions>()?
+        .options([("default_transaction_read_only", "on")]);
+    Ok(PgPoolOptions::new().connect_with(options).await?)
+}
+
+fn is_postgres_url(dataset: &str) -> bool {
+    dataset.starts_with("postgres://") || dataset.starts_with("postgresql://")
+}
+
+fn is_duckdb_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext == "duckdb" || ext == "ddb")
+}
+
+#[cfg(feature = "duckdb")]
+fn load_duckdb(path: &Path) -> Result<Database> {
+    tracing::info!("🦆 Abriendo {} en solo lectura", path.display());
+    Ok(Database::DuckDb(duckdb::DuckDb::open(path)?))
+}
+
+#[cfg(not(feature = "duckdb"))]
+fn load_duckdb(path: &Path) -> Result<Database> {
+    Err(eyre!("{}", t!("db.duckdb_disabled", path = path.display())))
+}
+
+/// Nombre con el que se identifica la base de datos (logs, prompt).
+/// De las URLs de Postgres solo se conserva el host y la base de datos.
+pub fn name(cfg: &DbConfig) -> String {
+    match &cfg.dataset {
+        Some(dataset) if is_postgres_url(dataset) => match dataset.rsplit_once('@') {
+            Some((_, host)) => format!("postgres://{}", host),
+            None => dataset.clone(),
+        },
+        Some(dataset) => dataset.clone(),
+        None => cfg.file.clone(),
+    }
+}
+
+/// Huella de la base de datos: su nombre más el tamaño y la fecha de
+/// modificación de sus ficheros. Cambia en cuanto cambian los datos
+/// (salvo en Postgres, donde solo se puede usar la URL)
+pub fn fingerprint(cfg: &DbConfig) -> String {
+    let name = name(cfg);
+    let path = Path::new(cfg.dataset.as_deref().unwrap_or(&cfg.file));
+
+    let files = if path.is_dir() {
+        import::dataset_files(path).unwrap_or_default()
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    let mut fingerprint = name;
+    for file in files {
+        let Ok(metadata) = std::fs::metadata(&file) else {
+            continue;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_secs());
+        fingerprint.push_str(&format!(
+            "|{}:{}:{}",
+            file.display(),
+            metadata.len(),
+            modified
+        ));
+    }
+    fingerprint
+}
+
+/// Un fichero SQLite (en solo lectura), un CSV/JSON suelto o un directorio de CSV/JSON
+async fn load_dataset(path: &Path) -> Result<Pool<Sqlite>> {
+    if path.is_dir() {
+        let files = import::dataset_files(path)?;
+        if files.is_empty() {
+            return Err(eyre!("{}", t!("db.empty_dataset", path = path.display())));
+        }
+        return import::import(&files).await;
+    }
+
+    if !path.exists() {
+        return Err(eyre!("{}", t!("db.missing_dataset", path = path.display())));
+    }
+
+    if import::is_importable(path) {
+        return import::import(&[path.to_path_buf()]).await;
+    }
+
+    tracing::info!("📂 Abriendo {} en solo lectura", path.display());
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    Ok(SqlitePool::connect_with(options).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("sakila-test-{}-{}", std::process::id(), name))
+    }
+
+    async fn create_actor_db(path: &Path) {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::raw_sql("CREATE TABLE actor (id INTEGER); INSERT INTO actor VALUES (1);")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+    }
+
+    /// Ninguna escritura llega a la base de datos, empiece como empiece la query
+    async fn assert_read_only(db: &Database) {
+        assert_eq!(db.query("SELECT id FROM actor").await.unwrap().len(), 1);
+        for write in [
+            "INSERT INTO actor VALUES (2)",
+            "REPLACE INTO actor VALUES (2)",
+            "CREATE TABLE film (id INTEGER)",
+            "ALTER TABLE actor ADD COLUMN name TEXT",
+            "WITH old AS (SELECT id FROM actor) DELETE FROM actor WHERE id IN (SELECT id FROM old)",
+        ] {
+            assert!(db.query(write).await.is_err(), "{}", write);
+        }
+        assert_eq!(db.query("SELECT id FROM actor").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sqlite_dataset_is_read_only() {
+        let path = temp_path("ro.db");
+        create_actor_db(&path).await;
+
+        let db = Database::Sqlite(load_dataset(&path).await.unwrap());
+        assert_read_only(&db).await;
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sakila_database_is_read_only() {
+        let path = temp_path("sakila-ro.db");
+        create_actor_db(&path).await;
+
+        let cfg = DbConfig {
+            url: String::new(),
+            file: path.display().to_string(),
+            sha256: None,
+            offline: true,
+            scripts: Vec::new(),
+            dataset: None,
+        };
+        let db = load(&cfg).await.unwrap();
+        assert_read_only(&db).await;
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn imported_dataset_is_read_only() {
+        let dir = temp_path("import");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("actor.csv"), "id\n1\n").unwrap();
+
+        let db = Database::Sqlite(load_dataset(&dir).await.unwrap());
+        assert_read_only(&db).await;
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Necesita un servidor: `SAKILA_TEST_POSTGRES=postgres://... cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_session_is_read_only() {
+        let url = std::env::var("SAKILA_TEST_POSTGRES").expect("SAKILA_TEST_POSTGRES");
+        let pool = connect_postgres(&url).await.unwrap();
+
+        let read_only: String = sqlx::query_scalar("SHOW default_transaction_read_only")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(read_only, "on");
+        assert!(
+            sqlx::query("CREATE TABLE sakila_read_only_test (id INTEGER)")
+                .execute(&pool)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use color_eyre::Result;
+use serde_json::Value;
+use sqlx::{Pool, Postgres, Row, types::Json};
+
+/// Postgres devuelve cada fila como JSON: así NUMERIC, fechas, arrays...
+/// llegan ya convertidos sin decodificar cada tipo por separado
+pub(super) async fn query(pool: &Pool<Postgres>, query: &str) -> Result<Vec<Value>> {
+    let query = query.trim().trim_end_matches(';');
+    let wrapped = format!("SELECT row_to_json(t) FROM ({}) t", query);
+    let rows = sqlx::query(&wrapped).fetch_all(pool).await?;
+
+    rows.iter()
+        .map(|row| Ok(row.try_get::<Json<Value>, _>(0)?.0))
+        .collect()
+}
+
+/// (tabla, columna, tipo) de las tablas de usuario; las que no están en
+/// el esquema `public` llevan el esquema delante
+pub(super) async fn columns(pool: &Pool<Postgres>) -> Result<Vec<(String, String, String)>> {
+    let rows = sqlx::query(
+        "SELECT CASE WHEN c.table_schema = 'public' THEN c.table_name
+                     ELSE c.table_schema || '.' || c.table_name END,
+                c.column_name, c.data_type
+         FROM information_schema.columns c
+         JOIN information_schema.tables t
+           ON t.table_schema = c.table_schema AND t.table_name = c.table_name
+         WHERE c.table_schema NOT IN ('pg_catalog', 'information_schema')
+           AND t.table_type = 'BASE TABLE'
+         ORDER BY c.table_schema, c.table_name, c.ordinal_position",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| Ok((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)))
+        .collect()
+}
+
+/// Plan en texto, una línea por nodo
+pub(super) async fn explain(pool: &Pool<Postgres>, query: &str) -> Result<Vec<String>> {
+    let query = query.trim().trim_end_matches(';');
+    let rows = sqlx::query(&format!("EXPLAIN {}", query))
+        .fetch_all(pool)
+        .await?;
+
+    rows.iter().map(|row| Ok(row.try_get(0)?)).collect()
+}
@@ -0,0 +1,331 @@
+use std::path::{Path, PathBuf};
+
+use color_eyre::{Result, eyre::eyre};
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{StatusCode, header::RANGE};
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite, SqlitePool, sqlite::SqliteConnectOptions};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+
+use crate::{config::DbConfig, t};
+
+/// Base de datos Sakila: se descarga (o se construye con los scripts SQL)
+/// la primera vez y se comprueba su integridad en cada arranque
+pub(super) async fn load(cfg: &DbConfig) -> Result<Pool<Sqlite>> {
+    let path = Path::new(&cfg.file);
+
+    // Solo se vuelve a crear si el fichero está dañado; otros errores (base de
+    // datos bloqueada, permisos) se devuelven sin tocar el fichero
+    if path.exists() {
+        match open_verified(path).await? {
+            Opened::Ready(pool) => return Ok(pool),
+            Opened::Damaged(reason) => {
+                tracing::warn!(
+                    "⚠️  {} está dañada ({}); se vuelve a crear",
+                    cfg.file,
+                    reason
+                );
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    if cfg.offline {
+        build_from_scripts(&cfg.scripts, path).await?;
+    } else if let Err(err) = download(cfg, path).await {
+        if !scripts_available(&cfg.scripts) {
+            return Err(err.wrap_err("Falló la descarga y no hay scripts SQL (db.scripts)"));
+        }
+        tracing::warn!("⚠️  Falló la descarga ({}); se usan los scripts SQL", err);
+        build_from_scripts(&cfg.scripts, path).await?;
+    }
+
+    match open_verified(path).await? {
+        Opened::Ready(pool) => Ok(pool),
+        Opened::Damaged(reason) => Err(eyre!(
+            "{}",
+            t!("db.damaged", file = cfg.file, reason = reason)
+        )),
+    }
+}
+
+enum Opened {
+    Ready(Pool<Sqlite>),
+    /// Falla `PRAGMA integrity_check` o el fichero no es una base de datos
+    Damaged(String),
+}
+
+/// Abre la base de datos en solo lectura y ejecuta `PRAGMA integrity_check`
+async fn open_verified(path: &Path) -> Result<Opened> {
+    let options = SqliteConnectOptions::new().filename(path).read_only(true);
+    let pool = match SqlitePool::connect_with(options).await {
+        Ok(pool) => pool,
+        Err(err) if is_damaged(&err) => return Ok(Opened::Damaged(err.to_string())),
+        Err(err) => return Err(err.into()),
+    };
+
+    let problems: Vec<String> = match sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&pool)
+        .await
+    {
+        Ok(problems) => problems,
+        Err(err) if is_damaged(&err) => {
+            pool.close().await;
+            return Ok(Opened::Damaged(err.to_string()));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if problems.iter().any(|problem| problem != "ok") {
+        pool.close().await;
+        return Ok(Opened::Damaged(format!(
+            "integrity_check: {}",
+            problems.join("; ")
+        )));
+    }
+
+    Ok(Opened::Ready(pool))
+}
+
+/// SQLITE_CORRUPT (11) o SQLITE_NOTADB (26), también sus códigos extendidos
+fn is_damaged(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|err| err.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 11 | 26))
+}
+
+/// Descarga a un fichero `.part` (reanudando si ya existe), comprueba el
+/// SHA-256 y solo entonces lo mueve a su ruta final
+async fn download(cfg: &DbConfig, path: &Path) -> Result<()> {
+    let part = with_suffix(path, "part");
+    let offset = match tokio::fs::metadata(&part).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+
+    tracing::info!("Downloading database from {}...", cfg.url);
+
+    let mut request = reqwest::Client::new().get(&cfg.url);
+    if offset > 0 {
+        tracing::info!("⏯  Reanudando la descarga desde el byte {}", offset);
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let response = request.send().await?;
+
+    // 206: el servidor continúa donde se quedó; 200: envía el fichero entero;
+    // 416: el .part ya estaba completo
+    let (mut file, mut downloaded) = match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            let file = OpenOptions::new().append(true).open(&part).await?;
+            (Some(file), offset)
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => (None, offset),
+        status if status.is_success() => {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&part)
+                .await?;
+            (Some(file), 0)
+        }
+        status => {
+            return Err(eyre!("{}", t!("db.download_failed", status = status)));
+        }
+    };
+
+    if let Some(file) = file.as_mut() {
+        let pb = match response.content_length() {
+            Some(size) => {
+                let pb = ProgressBar::new(downloaded + size);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
+                        .progress_chars("#>-")
+                );
+                pb.set_message(t!("db.downloading"));
+                pb
+            }
+            None => {
+                let pb = ProgressBar::new_spinner();
+                pb.set_message(t!("db.downloading_unknown"));
+                pb
+            }
+        };
+        pb.set_position(downloaded);
+
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            downloaded += chunk.len() as u64;
+            pb.set_position(downloaded);
+        }
+        file.flush().await?;
+
+        pb.finish_with_message(t!("db.downloaded"));
+    }
+
+    let digest = sha256_file(&part).await?;
+    match &cfg.sha256 {
+        Some(expected) if !expected.eq_ignore_ascii_case(&digest) => {
+            // Un .part corrupto no se puede reanudar: se descarta
+            tokio::fs::remove_file(&part).await?;
+            return Err(eyre!(
+                "{}",
+                t!("db.checksum_mismatch", expected = expected, actual = digest)
+            ));
+        }
+        Some(_) => tracing::info!("✅ SHA-256 verificado"),
+        None => tracing::warn!(
+            "⚠️  Sin db.sha256 en la configuración: el fichero no se ha verificado ({})",
+            digest
+        ),
+    }
+
+    tokio::fs::rename(&part, path).await?;
+    tracing::info!(
+        "Database file downloaded and saved to {} ({} bytes)",
+        cfg.file,
+        downloaded
+    );
+
+    Ok(())
+}
+
+/// Construye la base de datos sin red ejecutando los scripts de esquema y datos
+async fn build_from_scripts(scripts: &[String], path: &Path) -> Result<()> {
+    if !scripts_available(scripts) {
+        return Err(eyre!(
+            "{}",
+            t!("db.missing_scripts", scripts = scripts.join(", "))
+        ));
+    }
+
+    let building = with_suffix(path, "build");
+    if building.exists() {
+        tokio::fs::remove_file(&building).await?;
+    }
+
+    let options = SqliteConnectOptions::new()
+        .filename(&building)
+        .create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await?;
+    for script in scripts {
+        tracing::info!("📜 Ejecutando {}", script);
+        let sql = tokio::fs::read_to_string(script).await?;
+        sqlx::raw_sql(&sql).execute(&pool).await?;
+    }
+    pool.close().await;
+
+    tokio::fs::rename(&building, path).await?;
+    tracing::info!("✅ Base de datos construida en {}", path.display());
+    Ok(())
+}
+
+fn scripts_available(scripts: &[String]) -> bool {
+    !scripts.is_empty() && scripts.iter().all(|script| Path::new(script).exists())
+}
+
+async fn sha256_file(path: &Path) -> Result<String> {
+    let content = tokio::fs::read(path).await?;
+    Ok(Sha256::digest(&content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// `data/sakila.db` -> `data/sakila.db.part`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sakila-test-{}-{}", std::process::id(), name))
+    }
+
+    #[tokio::test]
+    async fn garbage_file_is_damaged() {
+        let path = temp_path("garbage.db");
+        std::fs::write(&path, vec![b'x'; 4096]).unwrap();
+
+        let opened = open_verified(&path).await.unwrap();
+        assert!(matches!(opened, Opened::Damaged(_)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_returned() {
+        // Un directorio no se puede abrir, pero no es un fichero dañado
+        let path = temp_path("dir.db");
+        std::fs::create_dir_all(&path).unwrap();
+
+        assert!(open_verified(&path).await.is_err());
+        assert!(path.exists());
+
+        std::fs::remove_dir(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn bundled_schema_builds() {
+        let path = temp_path("schema.db");
+        let schema = format!("{}/sql/sakila-schema.sql", env!("CARGO_MANIFEST_DIR"));
+        build_from_scripts(&[schema], &path).await.unwrap();
+
+        let pool = match open_verified(&path).await.unwrap() {
+            Opened::Ready(pool) => pool,
+            Opened::Damaged(reason) => panic!("{}", reason),
+        };
+        let tables: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(tables, 16);
+        pool.close().await;
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn missing_scripts_are_reported() {
+        let path = temp_path("missing.db");
+        let err = build_from_scripts(&["sql/no-existe.sql".to_string()], &path)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("make sql-data"));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn valid_database_is_ready() {
+        let path = temp_path("valid.db");
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        sqlx::raw_sql("CREATE TABLE actor (id INTEGER)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let opened = open_verified(&path).await.unwrap();
+        assert!(matches!(opened, Opened::Ready(_)));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
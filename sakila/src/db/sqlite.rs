@@ -0,0 +1,78 @@
+use color_eyre::Result;
+use serde_json::{Value, json};
+use sqlx::{Column, Pool, Row, Sqlite};
+
+pub(super) async fn query(pool: &Pool<Sqlite>, query: &str) -> Result<Vec<Value>> {
+    let rows = sqlx::query(query).fetch_all(pool).await?;
+
+    let mut results = Vec::new();
+    for row in &rows {
+        let mut obj = serde_json::Map::new();
+
+        for (i, column) in row.columns().iter().enumerate() {
+            let column_name = column.name();
+
+            // Intentar tipos en orden (de más específico a más genérico)
+            let value =
+                // Intentar i32 primero (SMALLINT en SQLite)
+                if let Ok(Some(v)) = row.try_get::<Option<i32>, _>(i) {
+                    json!(v)
+                }
+                // Intentar i64 (INTEGER, BIGINT)
+                else if let Ok(Some(v)) = row.try_get::<Option<i64>, _>(i) {
+                    json!(v)
+                }
+                // Intentar u32 (por si acaso)
+                else if let Ok(Some(v)) = row.try_get::<Option<u32>, _>(i) {
+                    json!(v)
+                }
+                // Intentar f64 (REAL, FLOAT, DOUBLE)
+                else if let Ok(Some(v)) = row.try_get::<Option<f64>, _>(i) {
+                    json!(v)
+                }
+                // Intentar String (TEXT, VARCHAR, CHAR, TIMESTAMP)
+                else if let Ok(Some(v)) = row.try_get::<Option<String>, _>(i) {
+                    json!(v)
+                }
+                // Intentar bool
+                else if let Ok(Some(v)) = row.try_get::<Option<bool>, _>(i) {
+                    json!(v)
+                }
+                // Si todo falla o es NULL explícito
+                else {
+                    json!(null)
+                };
+
+            obj.insert(column_name.to_string(), value);
+        }
+
+        results.push(Value::Object(obj));
+    }
+
+    Ok(results)
+}
+
+/// (tabla, columna, tipo) de todas las tablas de usuario
+pub(super) async fn columns(pool: &Pool<Sqlite>) -> Result<Vec<(String, String, String)>> {
+    let rows = sqlx::query(
+        "SELECT m.name, p.name, p.type
+         FROM sqlite_master m JOIN pragma_table_info(m.name) p
+         WHERE m.type = 'table' AND m.name NOT LIKE 'sqlite_%'
+         ORDER BY m.name, p.cid",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|row| Ok((row.try_get(0)?, row.try_get(1)?, row.try_get(2)?)))
+        .collect()
+}
+
+/// Una línea por paso del plan (columna `detail` de EXPLAIN QUERY PLAN)
+pub(super) async fn explain(pool: &Pool<Sqlite>, query: &str) -> Result<Vec<String>> {
+    let rows = sqlx::query(&format!("EXPLAIN QUERY PLAN {}", query))
+        .fetch_all(pool)
+        .await?;
+
+    rows.iter().map(|row| Ok(row.try_get("detail")?)).collect()
+}
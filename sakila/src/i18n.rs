@@ -0,0 +1,208 @@
+use std::{collections::HashMap, fmt::Display, sync::OnceLock};
+
+use color_eyre::Result;
+use config::{Config, File, FileFormat};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Idioma de los mensajes de la interfaz y de los prompts
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    En,
+    #[default]
+    Es,
+}
+
+impl Locale {
+    /// Acepta códigos como `es`, `en-US` o `es_ES.UTF-8`
+    pub fn parse(code: &str) -> Option<Self> {
+        match code.get(..2)?.to_ascii_lowercase().as_str() {
+            "es" => Some(Locale::Es),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    /// SAKILA_LOCALE, después `locale` en la configuración y por último
+    /// el idioma del sistema (LC_ALL, LC_MESSAGES, LANG)
+    pub fn detect(configured: Option<Locale>) -> Self {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        env("SAKILA_LOCALE")
+            .and_then(|code| Self::parse(&code))
+            .or(configured)
+            .or_else(|| {
+                ["LC_ALL", "LC_MESSAGES", "LANG"]
+                    .into_iter()
+                    .find_map(env)
+                    .and_then(|code| Self::parse(&code))
+            })
+            .unwrap_or_default()
+    }
+
+    fn catalog(self) -> &'static str {
+        match self {
+            Locale::Es => include_str!("../locales/es.yaml"),
+            Locale::En => include_str!("../locales/en.yaml"),
+        }
+    }
+}
+
+/// Texto que depende del idioma (prompts de la configuración)
+#[derive(Debug, Deserialize, Clone)]
+pub struct Localized<T> {
+    pub es: T,
+    pub en: T,
+}
+
+impl<T> Localized<T> {
+    pub fn get(&self) -> &T {
+        match locale() {
+            Locale::Es => &self.es,
+            Locale::En => &self.en,
+        }
+    }
+}
+
+struct Catalog {
+    locale: Locale,
+    messages: HashMap<String, String>,
+    /// El catálogo en español es la referencia si falta una clave
+    fallback: HashMap<String, String>,
+}
+
+impl Catalog {
+    fn load(locale: Locale) -> Result<Self> {
+        Ok(Self {
+            locale,
+            messages: parse(locale.catalog())?,
+            fallback: parse(Locale::Es.catalog())?,
+        })
+    }
+}
+
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+/// Carga el catálogo del idioma. Solo la primera llamada tiene efecto
+pub fn init(locale: Locale) -> Result<()> {
+    let catalog = Catalog::load(locale)?;
+    let _ = CATALOG.set(catalog);
+    Ok(())
+}
+
+pub fn locale() -> Locale {
+    catalog().locale
+}
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| {
+        Catalog::load(Locale::default()).unwrap_or_else(|_| Catalog {
+            locale: Locale::default(),
+            messages: HashMap::new(),
+            fallback: HashMap::new(),
+        })
+    })
+}
+
+/// Mensaje sin parámetros; si la clave no existe se devuelve la clave
+pub fn text(key: &'static str) -> &'static str {
+    let catalog = catalog();
+    match catalog.messages.get(key).or(catalog.fallback.get(key)) {
+        Some(message) => message,
+        None => {
+            tracing::warn!(key, "Mensaje sin traducir");
+            key
+        }
+    }
+}
+
+/// Mensaje con parámetros `{nombre}`. Se sustituyen en una sola pasada:
+/// un valor que contenga `{otro}` (la pregunta del usuario) se deja tal cual
+pub fn format(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    let mut message = String::new();
+    let mut rest = text(key);
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let arg = tail.find('}').and_then(|end| {
+            args.iter()
+                .find(|(name, _)| *name == &tail[1..end])
+                .map(|(_, value)| (end, value))
+        });
+        match arg {
+            Some((end, value)) => {
+                message.push_str(&value.to_string());
+                rest = &tail[end + 1..];
+            }
+            None => {
+                message.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    message.push_str(rest);
+    message
+}
+
+/// Mensaje del catálogo: `t!("repl.bye")` o `t!("agent.exported", count = n, file = path)`
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::text($key)
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::format(
+            $key,
+            &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+],
+        )
+    };
+}
+
+/// Catálogo YAML anidado a claves con puntos: `repl.bye`
+fn parse(source: &str) -> Result<HashMap<String, String>> {
+    let tree: Value = Config::builder()
+        .add_source(File::from_str(source, FileFormat::Yaml))
+        .build()?
+        .try_deserialize()?;
+
+    let mut messages = HashMap::new();
+    flatten("", &tree, &mut messages);
+    Ok(messages)
+}
+
+fn flatten(prefix: &str, value: &Value, messages: &mut HashMap<String, String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let key = match prefix {
+                    "" => key.clone(),
+                    prefix => format!("{}.{}", prefix, key),
+                };
+                flatten(&key, value, messages);
+            }
+        }
+        Value::String(text) => {
+            messages.insert(prefix.to_string(), text.clone());
+        }
+        other => {
+            messages.insert(prefix.to_string(), other.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_not_substituted_again() {
+        let question = "¿Qué es {answer}?";
+        assert_eq!(
+            format(
+                "{question} -> {answer} {unknown}",
+                &[("question", &question), ("answer", &42),]
+            ),
+            "¿Qué es {answer}? -> 42 {unknown}"
+        );
+    }
+}
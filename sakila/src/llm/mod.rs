@@ -1,6 +1,11 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
-use crate::{chat::Message, config::AppConfig, device};
+use crate::{
+    cancel::CancelToken,
+    chat::Message,
+    config::{AppConfig, SamplingOverride},
+    device,
+};
 use candle_core::{Device, Tensor, quantized::gguf_file};
 use candle_transformers::{
     generation::{LogitsProcessor, Sampling},
@@ -8,10 +13,122 @@ use candle_transformers::{
 };
 use color_eyre::{Result, eyre::Error};
 use hf_hub::api::tokio::Api;
-use tera::{Context, Tera};
+use serde::Serialize;
+use template::ChatTemplate;
 use tokenizers::Tokenizer;
+use tracing::{field::Empty, span::EnteredSpan};
 
 pub mod stream;
+pub mod template;
+pub mod worker;
+
+/// Tokens por span de decodificación
+const DECODE_BATCH: usize = 32;
+
+/// Span que agrupa un bloque de pasos de decodificación
+struct DecodeBatch {
+    span: EnteredSpan,
+    started: Instant,
+    first_token: usize,
+}
+
+impl DecodeBatch {
+    fn start(first_token: usize) -> Self {
+        Self {
+            span: tracing::info_span!(
+                "decode",
+                first_token,
+                tokens = Empty,
+                tokens_per_sec = Empty
+            )
+            .entered(),
+            started: Instant::now(),
+            first_token,
+        }
+    }
+
+    fn finish(self, generated: usize) {
+        let tokens = generated - self.first_token;
+        let elapsed = self.started.elapsed().as_secs_f64().max(1e-9);
+        self.span.record("tokens", tokens);
+        self.span.record("tokens_per_sec", tokens as f64 / elapsed);
+    }
+}
+
+/// Motivo por el que terminó la generación
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    Eos,
+    /// Uno de los tokens de parada adicionales
+    StopToken(String),
+    /// Una de las secuencias de parada (incluida al final del texto)
+    StopSequence(String),
+    MaxLength,
+    Cancelled,
+}
+
+/// Tokens procesados y velocidad de una generación
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub prompt_tokens: usize,
+    pub generated_tokens: usize,
+    pub ttft_ms: u64,
+    pub tokens_per_sec: f64,
+}
+
+/// Parámetros de muestreo con los que se genera
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SamplingParams {
+    pub temperature: f64,
+    pub top_p: f64,
+    pub top_k: usize,
+    pub seed: u64,
+    pub max_length: usize,
+}
+
+impl SamplingParams {
+    pub fn with(self, overrides: &SamplingOverride) -> Self {
+        Self {
+            temperature: overrides.temperature.unwrap_or(self.temperature),
+            top_p: overrides.top_p.unwrap_or(self.top_p),
+            top_k: overrides.top_k.unwrap_or(self.top_k),
+            max_length: overrides.max_length.unwrap_or(self.max_length),
+            ..self
+        }
+    }
+
+    fn logits_processor(&self) -> LogitsProcessor {
+        LogitsProcessor::from_sampling(
+            self.seed,
+            Sampling::TopKThenTopP {
+                temperature: self.temperature,
+                k: self.top_k,
+                p: self.top_p,
+            },
+        )
+    }
+}
+
+/// Lo que el modelo recibió y generó exactamente, para depurar
+#[derive(Debug, Clone)]
+pub struct Trace {
+    /// Prompt renderizado con el chat template
+    pub prompt: String,
+    /// Texto generado sin limpiar, con los tokens especiales
+    pub raw: String,
+    pub sampling: SamplingParams,
+}
+
+/// Respuesta generada y cómo terminó
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub message: Message,
+    /// Razonamiento del modo thinking, fuera del mensaje guardado en memoria
+    pub reasoning: Option<String>,
+    pub stop_reason: StopReason,
+    pub usage: Usage,
+    pub trace: Trace,
+}
 
 pub struct Llm {
     device: Device,
@@ -19,11 +136,23 @@ pub struct Llm {
     eos_token: u32,
     model: Qwen3,
     logits_processor: LogitsProcessor,
-    prompt: Tera,
-    start_completion: String,
+    sampling: SamplingParams,
+    template: ChatTemplate,
     max_length: usize,
     kv_cache_offset: usize,
     banned_tokens: Vec<u32>,
+    stop_tokens: Vec<(u32, String)>,
+    stop_sequences: Vec<String>,
+    think_start: String,
+    think_end: String,
+    thinking: Option<Thinking>,
+}
+
+/// Tokens que delimitan el razonamiento y su presupuesto
+struct Thinking {
+    start: u32,
+    end: u32,
+    budget: usize,
 }
 
 impl Llm {
@@ -67,10 +196,15 @@ impl Llm {
             .as_str(),
         );
 
+        let thinking_tags = [&config.tokenizer.think_start, &config.tokenizer.think_end];
+        let thinking_enabled = config.inference.thinking.enabled;
+
         let banned_tokens: Vec<u32> = config
             .tokenizer
             .banned_tokens
             .iter()
+            // En modo thinking las etiquetas <think> tienen que poder generarse
+            .filter(|token| !(thinking_enabled && thinking_tags.contains(token)))
             .filter_map(|token| {
                 let id = vocab.get(token.as_str()).copied();
                 if id.is_none() {
@@ -80,23 +214,46 @@ impl Llm {
             })
             .collect();
 
+        let stop_tokens: Vec<(u32, String)> = config
+            .tokenizer
+            .stop_tokens
+            .iter()
+            .filter_map(|token| {
+                let id = vocab.get(token.as_str()).copied();
+                if id.is_none() {
+                    tracing::warn!("⚠️  Token de parada '{}' no existe en vocabulario", token);
+                }
+                id.map(|id| (id, token.clone()))
+            })
+            .collect();
+
+        let thinking = if thinking_enabled {
+            let token_id = |token: &str| {
+                vocab.get(token).copied().ok_or_else(|| {
+                    Error::msg(format!("El tokenizer debe tener el token {}", token))
+                })
+            };
+            Some(Thinking {
+                start: token_id(&config.tokenizer.think_start)?,
+                end: token_id(&config.tokenizer.think_end)?,
+                budget: config.inference.thinking.budget,
+            })
+        } else {
+            None
+        };
+
         let mut model_file = std::fs::File::open(&model_path)?;
         let model_content = gguf_file::Content::read(&mut model_file)?;
         let model = Qwen3::from_gguf(model_content, &mut model_file, &device)?;
 
-        let logits_processor = LogitsProcessor::from_sampling(
-            config.llm.seed,
-            Sampling::TopKThenTopP {
-                temperature: config.inference.temperature,
-                k: config.inference.top_k,
-                p: config.inference.top_p,
-            },
-        );
-
-        let mut prompt = Tera::default();
-        prompt.add_raw_template("user", &config.tokenizer.user_template)?;
-        prompt.add_raw_template("assistant", &config.tokenizer.assistant_template)?;
-        prompt.add_raw_template("system", &config.tokenizer.system_template)?;
+        let sampling = SamplingParams {
+            temperature: config.inference.temperature,
+            top_p: config.inference.top_p,
+            top_k: config.inference.top_k,
+            seed: config.llm.seed,
+            max_length: config.inference.max_length,
+        };
+        let logits_processor = sampling.logits_processor();
 
         Ok(Self {
             device,
@@ -104,11 +261,16 @@ impl Llm {
             eos_token,
             model,
             logits_processor,
-            prompt,
-            start_completion: config.tokenizer.start_completion.clone(),
+            sampling,
+            template: ChatTemplate::new(&config.tokenizer, &config.inference.thinking)?,
             max_length: config.inference.max_length.clone(),
             kv_cache_offset: 0,
             banned_tokens,
+            stop_tokens,
+            stop_sequences: config.inference.stop_sequences.clone(),
+            think_start: config.tokenizer.think_start.clone(),
+            think_end: config.tokenizer.think_end.clone(),
+            thinking,
         })
     }
 
@@ -116,42 +278,142 @@ impl Llm {
         self.tokenizer.clone()
     }
 
+    pub fn get_template(&self) -> ChatTemplate {
+        self.template.clone()
+    }
+
+    /// Etiquetas que delimitan el razonamiento en el texto generado
+    pub fn get_think_tags(&self) -> (&str, &str) {
+        (&self.think_start, &self.think_end)
+    }
+
     #[allow(dead_code)]
-    pub fn chat(&mut self, messages: &Vec<Message>) -> Result<Message> {
-        let input_text = self.render(messages)?;
-        let content = self.run(&input_text, None)?;
+    pub fn chat(&mut self, messages: &[Message]) -> Result<Message> {
+        let input_text = self.template.render(messages)?;
+        let (content, _, _, _) = self.run(&input_text, None, None)?;
 
         Ok(Message::Assistant { content })
     }
 
     pub fn chat_stream(
         &mut self,
-        messages: &Vec<Message>,
+        messages: &[Message],
         callback: &mut dyn stream::TokenCallback,
-    ) -> Result<Message> {
-        let input_text = self.render(messages)?;
-        let content = self.run(&input_text, Some(callback))?;
-        Ok(Message::Assistant { content })
+        cancel: &CancelToken,
+        overrides: &SamplingOverride,
+    ) -> Result<Completion> {
+        let input_text = self.template.render(messages)?;
+
+        // Otros parámetros solo para esta petición: después se restauran
+        // el muestreador (con su estado) y la longitud máxima
+        let sampling = self.sampling.with(overrides);
+        let saved = (sampling != self.sampling).then(|| {
+            (
+                std::mem::replace(&mut self.logits_processor, sampling.logits_processor()),
+                std::mem::replace(&mut self.max_length, sampling.max_length),
+            )
+        });
+        let result = self.run(&input_text, Some(callback), Some(cancel));
+        if let Some((logits_processor, max_length)) = saved {
+            self.logits_processor = logits_processor;
+            self.max_length = max_length;
+        }
+        let (text, raw, stop_reason, usage) = result?;
+        let (reasoning, content) =
+            stream::split_reasoning(&text, &self.think_start, &self.think_end);
+        Ok(Completion {
+            message: Message::Assistant { content },
+            reasoning,
+            stop_reason,
+            usage,
+            trace: Trace {
+                prompt: input_text,
+                raw,
+                sampling,
+            },
+        })
+    }
+
+    /// Clasificación con salida restringida: devuelve el índice de la
+    /// etiqueta cuyo primer token es más probable como inicio de la respuesta
+    pub fn classify(&mut self, messages: &[Message], labels: &[String]) -> Result<usize> {
+        let _span = tracing::info_span!("classify", labels = labels.len()).entered();
+        // En modo thinking el primer token sería `<think>`, no una etiqueta
+        let input_text = self.template.render_direct(messages)?;
+        let logits = self.forward_prompt(&input_text);
+        self.clean_cache();
+        let logits = logits?.to_vec1::<f32>()?;
+
+        let mut best = (0, f32::NEG_INFINITY);
+        for (index, label) in labels.iter().enumerate() {
+            let encoded = self
+                .tokenizer
+                .encode(label.as_str(), false)
+                .map_err(Error::msg)?;
+            let Some(&token) = encoded.get_ids().first() else {
+                continue;
+            };
+            let score = logits[token as usize];
+            if score > best.1 {
+                best = (index, score);
+            }
+        }
+
+        Ok(best.0)
     }
 
     fn run(
         &mut self,
         text: &str,
         mut callback: Option<&mut dyn stream::TokenCallback>,
-    ) -> Result<String> {
+        cancel: Option<&CancelToken>,
+    ) -> Result<(String, String, StopReason, Usage)> {
+        let span = tracing::info_span!(
+            "generate",
+            prompt_tokens = Empty,
+            generated_tokens = Empty,
+            ttft_ms = Empty,
+            tokens_per_sec = Empty,
+            stop_reason = Empty,
+        );
+        let _guard = span.enter();
+
+        let started = Instant::now();
+        let mut thinking_tokens = None;
         let mut next_token = self.prefill(text)?;
+        next_token = self.enforce_thinking_budget(&mut thinking_tokens, next_token);
         let mut generated_tokens = vec![next_token];
+        let prompt_tokens = self.kv_cache_offset;
+        let ttft_ms = started.elapsed().as_millis() as u64;
+        span.record("prompt_tokens", prompt_tokens);
+        span.record("ttft_ms", ttft_ms);
 
         if let Some(cb) = callback.as_mut() {
             cb.on_token(next_token);
         }
 
+        // Decodificación propia para detectar secuencias de parada
+        let mut stop_decoder = stream::TextDecoder::new(self.tokenizer.clone());
+        let mut stop_matcher = stream::StopMatcher::new(&self.stop_sequences);
+        stop_decoder.push(next_token);
+
+        let decode_started = Instant::now();
+        let mut batch = DecodeBatch::start(generated_tokens.len());
+        let mut stop_reason = StopReason::MaxLength;
+
         while generated_tokens.len() < self.max_length {
+            let closed = callback.as_ref().is_some_and(|cb| cb.is_closed());
+            if closed || cancel.is_some_and(CancelToken::is_cancelled) {
+                stop_reason = StopReason::Cancelled;
+                break;
+            }
+
             let input = Tensor::new(&[next_token], &self.device)?.unsqueeze(0)?;
             let logits = self.model.forward(&input, self.kv_cache_offset)?;
             let logits = logits.squeeze(0)?;
             let logits = self.apply_logits_bias(&logits)?;
             next_token = self.logits_processor.sample(&logits)?;
+            next_token = self.enforce_thinking_budget(&mut thinking_tokens, next_token);
             generated_tokens.push(next_token);
             self.kv_cache_offset += 1;
 
@@ -160,22 +422,104 @@ impl Llm {
             }
 
             if next_token == self.eos_token {
+                stop_reason = StopReason::Eos;
                 break;
             }
+
+            if let Some((_, token)) = self.stop_tokens.iter().find(|(id, _)| *id == next_token) {
+                stop_reason = StopReason::StopToken(token.clone());
+                break;
+            }
+
+            // Las secuencias de parada no cuentan dentro del razonamiento
+            if !self.stop_sequences.is_empty() {
+                let text = stop_decoder.push(next_token);
+                let sequence = match text {
+                    Some(text) if thinking_tokens.is_none() => stop_matcher.push(&text),
+                    _ => None,
+                };
+                if let Some(sequence) = sequence {
+                    stop_reason = StopReason::StopSequence(sequence);
+                    break;
+                }
+            }
+
+            if generated_tokens.len() - batch.first_token >= DECODE_BATCH {
+                batch.finish(generated_tokens.len());
+                batch = DecodeBatch::start(generated_tokens.len());
+            }
         }
+        batch.finish(generated_tokens.len());
 
         if let Some(cb) = callback.as_mut() {
             cb.flush();
         }
 
-        let result = self
+        let decoded = generated_tokens.len() - 1;
+        let tokens_per_sec = decoded as f64 / decode_started.elapsed().as_secs_f64().max(1e-9);
+        span.record("generated_tokens", generated_tokens.len());
+        span.record("tokens_per_sec", tokens_per_sec);
+        span.record("stop_reason", format!("{:?}", stop_reason));
+
+        let mut result = self
             .tokenizer
             .decode(&generated_tokens, true)
             .map_err(Error::msg)?;
+        let raw = self
+            .tokenizer
+            .decode(&generated_tokens, false)
+            .map_err(Error::msg)?;
+
+        // El último token puede traer texto después de la secuencia de parada
+        let stop_at = match &stop_reason {
+            StopReason::StopSequence(sequence) => result
+                .rfind(sequence.as_str())
+                .map(|pos| pos + sequence.len()),
+            _ => None,
+        };
+        if let Some(end) = stop_at {
+            result.truncate(end);
+        }
 
         self.clean_cache();
 
-        Ok(result)
+        let usage = Usage {
+            prompt_tokens,
+            generated_tokens: generated_tokens.len(),
+            ttft_ms,
+            tokens_per_sec,
+        };
+
+        Ok((result, raw, stop_reason, usage))
+    }
+
+    /// Cuenta los tokens dentro de <think> y, al agotar el presupuesto,
+    /// sustituye el token muestreado por </think>
+    fn enforce_thinking_budget(&self, thinking_tokens: &mut Option<usize>, token: u32) -> u32 {
+        let Some(thinking) = &self.thinking else {
+            return token;
+        };
+
+        match *thinking_tokens {
+            None if token == thinking.start => {
+                *thinking_tokens = Some(0);
+                token
+            }
+            Some(_) if token == thinking.end => {
+                *thinking_tokens = None;
+                token
+            }
+            Some(count) if count >= thinking.budget => {
+                tracing::info!("🧠 Presupuesto de razonamiento agotado ({} tokens)", count);
+                *thinking_tokens = None;
+                thinking.end
+            }
+            Some(count) => {
+                *thinking_tokens = Some(count + 1);
+                token
+            }
+            None => token,
+        }
     }
 
     fn clean_cache(&mut self) {
@@ -184,17 +528,24 @@ impl Llm {
     }
 
     fn prefill(&mut self, text: &str) -> Result<u32> {
+        let logits = self.forward_prompt(text)?;
+        let logits = self.apply_logits_bias(&logits)?;
+        let next_token = self.logits_processor.sample(&logits)?;
+        Ok(next_token)
+    }
+
+    /// Procesa el prompt completo y devuelve los logits del siguiente token
+    fn forward_prompt(&mut self, text: &str) -> Result<Tensor> {
         let encoded = self.tokenizer.encode(text, true).map_err(Error::msg)?;
         let tokens = encoded.get_ids();
+        let _span = tracing::info_span!("prefill", prompt_tokens = tokens.len()).entered();
         let input = Tensor::new(&tokens[..], &self.device)?.unsqueeze(0)?;
         let logits = self.model.forward(&input, self.kv_cache_offset)?;
         let logits = logits.squeeze(0)?;
-        let logits = self.apply_logits_bias(&logits)?;
 
         // Recalculate the KV cache size
-        let next_token = self.logits_processor.sample(&logits)?;
         self.kv_cache_offset += tokens.len();
-        Ok(next_token)
+        Ok(logits)
     }
 
     fn apply_logits_bias(&self, logits: &Tensor) -> Result<Tensor> {
@@ -206,40 +557,4 @@ impl Llm {
 
         Ok(Tensor::from_vec(logits_vec, logits.shape(), &self.device)?)
     }
-
-    fn render(&self, messages: &Vec<Message>) -> Result<String> {
-        let text_messages: Vec<String> = messages
-            .iter()
-            .map(|message| match message {
-                Message::System { content } => self.render_system(content),
-                Message::User { content } => self.render_user(content),
-                Message::Assistant { content } => self.render_assistant(content),
-            })
-            .collect::<Result<Vec<String>>>()?;
-
-        let mut rendered = text_messages.join("");
-        rendered.push_str(&self.start_completion);
-        Ok(rendered)
-    }
-
-    fn render_system(&self, message: &str) -> Result<String> {
-        let mut context = Context::new();
-        context.insert("message", message);
-        let msg = self.prompt.render("system", &context)?;
-        Ok(msg)
-    }
-
-    fn render_assistant(&self, message: &str) -> Result<String> {
-        let mut context = Context::new();
-        context.insert("message", message);
-        let msg = self.prompt.render("assistant", &context)?;
-        Ok(msg)
-    }
-
-    fn render_user(&self, message: &str) -> Result<String> {
-        let mut context = Context::new();
-        context.insert("message", message);
-        let msg = self.prompt.render("user", &context)?;
-        Ok(msg)
-    }
 }
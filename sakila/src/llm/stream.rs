@@ -1,20 +1,27 @@
-use std::{io::Write, sync::Arc};
+use std::sync::Arc;
 
 use tokenizers::Tokenizer;
 
 pub trait TokenCallback {
     fn on_token(&mut self, token_id: u32);
     fn flush(&mut self);
+
+    /// Nadie espera ya los tokens: la generación puede parar
+    fn is_closed(&self) -> bool {
+        false
+    }
 }
 
-pub struct PrintCallback {
+/// Decodificación incremental: convierte tokens en fragmentos de texto
+/// sin cortar caracteres multibyte a la mitad
+pub struct TextDecoder {
     all_tokens: Vec<u32>,
     tokenizer: Arc<Tokenizer>,
     prev_index: usize,
     current_index: usize,
 }
 
-impl PrintCallback {
+impl TextDecoder {
     pub fn new(tokenizer: Arc<Tokenizer>) -> Self {
         Self {
             all_tokens: Vec::new(),
@@ -23,75 +30,322 @@ impl PrintCallback {
             current_index: 0,
         }
     }
-}
 
-impl TokenCallback for PrintCallback {
-    fn on_token(&mut self, token_id: u32) {
+    /// Añade un token y devuelve el texto nuevo, si ya se puede mostrar
+    pub fn push(&mut self, token_id: u32) -> Option<String> {
         self.all_tokens.push(token_id);
 
-        // Decodificar desde prev_index hasta current_index (texto ya impreso)
-        let prev_text = if self.current_index > self.prev_index {
-            self.tokenizer
-                .decode(&self.all_tokens[self.prev_index..self.current_index], true)
-                .unwrap_or_default()
-        } else {
-            String::new()
-        };
+        // Decodificar desde prev_index hasta current_index (texto ya emitido)
+        let prev_text = self.decode(self.prev_index, self.current_index);
 
         // Decodificar desde prev_index hasta el final (incluye nuevo token)
-        let current_text = self
-            .tokenizer
-            .decode(&self.all_tokens[self.prev_index..], true)
-            .unwrap_or_default();
+        let current_text = self.decode(self.prev_index, self.all_tokens.len());
 
-        // Solo imprimir si:
+        // Solo emitir si:
         // 1. El texto creció
         // 2. El último carácter NO es � (replacement char)
         if current_text.len() > prev_text.len() {
             let last_char = current_text.chars().last();
 
-            // Imprimir si el último char NO es � (carácter incompleto)
+            // Emitir si el último char NO es � (carácter incompleto)
             if last_char != Some('�') {
-                let new_text = &current_text[prev_text.len()..];
-                print!("{}", new_text);
-                std::io::stdout().flush().ok();
+                let new_text = current_text[prev_text.len()..].to_string();
 
                 // Actualizar índices
                 self.prev_index = self.current_index;
                 self.current_index = self.all_tokens.len();
+                return Some(new_text);
             }
         }
+
+        None
+    }
+
+    /// Devuelve el resto que no se emitió y reinicia el decodificador
+    pub fn finish(&mut self) -> Option<String> {
+        let prev_text = self.decode(self.prev_index, self.current_index);
+        let full_text = self.decode(self.prev_index, self.all_tokens.len());
+
+        let remaining = if full_text.len() > prev_text.len() {
+            Some(full_text[prev_text.len()..].to_string())
+        } else {
+            None
+        };
+
+        self.all_tokens.clear();
+        self.prev_index = 0;
+        self.current_index = 0;
+
+        remaining
     }
 
-    fn flush(&mut self) {
-        // Decodificar el resto que no se imprimió
-        let prev_text = if self.current_index > self.prev_index {
+    fn decode(&self, start: usize, end: usize) -> String {
+        if end > start {
             self.tokenizer
-                .decode(&self.all_tokens[self.prev_index..self.current_index], true)
+                .decode(&self.all_tokens[start..end], true)
                 .unwrap_or_default()
         } else {
             String::new()
-        };
+        }
+    }
+}
 
-        let full_text = self
-            .tokenizer
-            .decode(&self.all_tokens[self.prev_index..], true)
-            .unwrap_or_default();
+/// Fragmento de texto generado: razonamiento (dentro de <think>) o respuesta
+#[derive(Debug)]
+pub enum Segment {
+    Thinking(String),
+    Answer(String),
+}
 
-        if full_text.len() > prev_text.len() {
-            let remaining = &full_text[prev_text.len()..];
-            print!("{}", remaining);
-            std::io::stdout().flush().ok();
+/// Separa en streaming el razonamiento de la respuesta. Las etiquetas
+/// pueden llegar partidas entre fragmentos.
+pub struct ThinkSplitter {
+    start: String,
+    end: String,
+    in_think: bool,
+    pending: String,
+}
+
+impl ThinkSplitter {
+    pub fn new(start: &str, end: &str) -> Self {
+        Self {
+            start: start.to_string(),
+            end: end.to_string(),
+            in_think: false,
+            pending: String::new(),
         }
+    }
 
-        self.all_tokens.clear();
-        self.prev_index = 0;
-        self.current_index = 0;
+    pub fn push(&mut self, text: &str) -> Vec<Segment> {
+        self.pending.push_str(text);
+        let mut segments = Vec::new();
+
+        loop {
+            let tag = if self.in_think {
+                &self.end
+            } else {
+                &self.start
+            };
+
+            if let Some(pos) = self.pending.find(tag.as_str()) {
+                let before: String = self.pending.drain(..pos + tag.len()).take(pos).collect();
+                self.emit(&mut segments, before);
+                self.in_think = !self.in_think;
+                continue;
+            }
+
+            // Conservar un posible comienzo de etiqueta al final del texto
+            let keep = (1..=tag.len().min(self.pending.len()))
+                .rev()
+                .find(|&k| {
+                    let start = self.pending.len() - k;
+                    self.pending.is_char_boundary(start) && tag.starts_with(&self.pending[start..])
+                })
+                .unwrap_or(0);
+            let ready: String = self.pending.drain(..self.pending.len() - keep).collect();
+            self.emit(&mut segments, ready);
+            break;
+        }
+
+        segments
+    }
+
+    pub fn finish(&mut self) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let rest = std::mem::take(&mut self.pending);
+        self.emit(&mut segments, rest);
+        self.in_think = false;
+        segments
+    }
+
+    fn emit(&self, segments: &mut Vec<Segment>, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        segments.push(if self.in_think {
+            Segment::Thinking(text)
+        } else {
+            Segment::Answer(text)
+        });
+    }
+}
+
+/// Separa el razonamiento completo de la respuesta final. Siguiendo las
+/// recomendaciones de Qwen3, el razonamiento no se guarda en el historial.
+pub fn split_reasoning(text: &str, start: &str, end: &str) -> (Option<String>, String) {
+    let Some(open) = text.find(start) else {
+        return (None, text.to_string());
+    };
+
+    let before = &text[..open];
+    let inner = &text[open + start.len()..];
+
+    match inner.find(end) {
+        Some(close) => {
+            let reasoning = inner[..close].trim().to_string();
+            let after = inner[close + end.len()..].trim_start();
+            (Some(reasoning), format!("{}{}", before, after))
+        }
+        // Sin cierre: todo lo generado después de <think> es razonamiento
+        None => (Some(inner.trim().to_string()), before.to_string()),
+    }
+}
+
+/// Detecta secuencias de parada en el texto generado, aunque lleguen
+/// repartidas entre varios tokens
+pub struct StopMatcher {
+    sequences: Vec<String>,
+    tail: String,
+    window: usize,
+}
+
+impl StopMatcher {
+    pub fn new(sequences: &[String]) -> Self {
+        let window = sequences.iter().map(String::len).max().unwrap_or(0);
+        Self {
+            sequences: sequences.to_vec(),
+            tail: String::new(),
+            window,
+        }
+    }
+
+    /// Añade texto y devuelve la secuencia de parada encontrada, si hay alguna
+    pub fn push(&mut self, text: &str) -> Option<String> {
+        if self.sequences.is_empty() {
+            return None;
+        }
+
+        self.tail.push_str(text);
+        if let Some(sequence) = self
+            .sequences
+            .iter()
+            .find(|s| self.tail.contains(s.as_str()))
+        {
+            return Some(sequence.clone());
+        }
+
+        // Solo hace falta conservar lo justo para una secuencia partida
+        let mut cut = self.tail.len().saturating_sub(self.window);
+        while !self.tail.is_char_boundary(cut) {
+            cut += 1;
+        }
+        self.tail.drain(..cut);
+        None
     }
 }
 
-impl Drop for PrintCallback {
-    fn drop(&mut self) {
-        self.flush();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Razonamiento y respuesta concatenados, según los fragmentos
+    fn split(chunks: &[&str]) -> (String, String) {
+        let mut splitter = ThinkSplitter::new("<think>", "</think>");
+        let mut segments = Vec::new();
+        for chunk in chunks {
+            segments.extend(splitter.push(chunk));
+        }
+        segments.extend(splitter.finish());
+
+        let (mut thinking, mut answer) = (String::new(), String::new());
+        for segment in segments {
+            match segment {
+                Segment::Thinking(text) => thinking.push_str(&text),
+                Segment::Answer(text) => answer.push_str(&text),
+            }
+        }
+        (thinking, answer)
+    }
+
+    #[test]
+    fn think_tags_in_one_chunk() {
+        let (thinking, answer) = split(&["<think>pienso</think>respuesta"]);
+        assert_eq!(thinking, "pienso");
+        assert_eq!(answer, "respuesta");
+    }
+
+    #[test]
+    fn think_tags_split_across_chunks() {
+        let (thinking, answer) = split(&["<th", "ink>pien", "so</thi", "nk", ">resp", "uesta"]);
+        assert_eq!(thinking, "pienso");
+        assert_eq!(answer, "respuesta");
+
+        let (thinking, answer) =
+            split(&["<", "t", "h", "i", "n", "k", ">", "a", "<", "/", "think>b"]);
+        assert_eq!(thinking, "a");
+        assert_eq!(answer, "b");
+    }
+
+    #[test]
+    fn text_that_looks_like_a_tag() {
+        let (thinking, answer) = split(&["a <th", "e> b <", "/think"]);
+        assert_eq!(thinking, "");
+        assert_eq!(answer, "a <the> b </think");
+    }
+
+    #[test]
+    fn multibyte_text_around_tags() {
+        let (thinking, answer) = split(&["¿qué <thi", "nk>está ñ</", "think>sí"]);
+        assert_eq!(thinking, "está ñ");
+        assert_eq!(answer, "¿qué sí");
+    }
+
+    #[test]
+    fn unclosed_think_is_reasoning() {
+        let (thinking, answer) = split(&["<think>sin cierre"]);
+        assert_eq!(thinking, "sin cierre");
+        assert_eq!(answer, "");
+    }
+
+    #[test]
+    fn split_reasoning_from_full_text() {
+        let (reasoning, answer) = split_reasoning("<think> a </think>\n\nb", "<think>", "</think>");
+        assert_eq!(reasoning.as_deref(), Some("a"));
+        assert_eq!(answer, "b");
+
+        let (reasoning, answer) = split_reasoning("solo respuesta", "<think>", "</think>");
+        assert_eq!(reasoning, None);
+        assert_eq!(answer, "solo respuesta");
+    }
+
+    fn matcher(sequences: &[&str]) -> StopMatcher {
+        let sequences: Vec<String> = sequences.iter().map(|s| s.to_string()).collect();
+        StopMatcher::new(&sequences)
+    }
+
+    #[test]
+    fn stop_sequence_in_one_token() {
+        let mut stop = matcher(&["</sql>"]);
+        assert_eq!(stop.push("<sql>SELECT 1"), None);
+        assert_eq!(stop.push("</sql>"), Some("</sql>".to_string()));
+    }
+
+    #[test]
+    fn stop_sequence_across_tokens() {
+        let mut stop = matcher(&["</sql>"]);
+        for token in ["<sql>", "SELECT", " 1", "</", "s", "q"] {
+            assert_eq!(stop.push(token), None);
+        }
+        assert_eq!(stop.push("l>"), Some("</sql>".to_string()));
+    }
+
+    #[test]
+    fn partial_match_that_does_not_complete() {
+        let mut stop = matcher(&["</sql>"]);
+        assert_eq!(stop.push("a </s"), None);
+        assert_eq!(stop.push("pan>"), None);
+        assert_eq!(stop.push(" y </sq"), None);
+        assert_eq!(stop.push("l>"), Some("</sql>".to_string()));
+    }
+
+    #[test]
+    fn several_sequences_and_multibyte_text() {
+        let mut stop = matcher(&["</sql>", "FIN"]);
+        assert_eq!(stop.push("ñañañañañaña F"), None);
+        assert_eq!(stop.push("I"), None);
+        assert_eq!(stop.push("N"), Some("FIN".to_string()));
+
+        let mut stop = matcher(&[]);
+        assert_eq!(stop.push("</sql>"), None);
     }
 }
@@ -0,0 +1,75 @@
+use color_eyre::Result;
+use tera::{Context, Tera};
+
+use crate::{
+    chat::Message,
+    config::{ThinkingConfig, TokenizerConfig},
+};
+
+/// Chat template del modelo. Se puede clonar para renderizar prompts
+/// sin acceso al modelo (p. ej. desde el agente mientras el modelo genera).
+#[derive(Clone)]
+pub struct ChatTemplate {
+    prompt: Tera,
+    start_completion: String,
+    /// Inicio de la respuesta con el bloque de razonamiento ya cerrado
+    direct_completion: String,
+}
+
+impl ChatTemplate {
+    pub fn new(config: &TokenizerConfig, thinking: &ThinkingConfig) -> Result<Self> {
+        let mut prompt = Tera::default();
+        prompt.add_raw_template("user", &config.user_template)?;
+        prompt.add_raw_template("assistant", &config.assistant_template)?;
+        prompt.add_raw_template("system", &config.system_template)?;
+
+        // En modo thinking el modelo abre su propio bloque <think>
+        let start_completion = if thinking.enabled {
+            config.thinking_start_completion.clone()
+        } else {
+            config.start_completion.clone()
+        };
+
+        Ok(Self {
+            prompt,
+            start_completion,
+            direct_completion: config.start_completion.clone(),
+        })
+    }
+
+    /// Prompt completo, listo para que el asistente empiece a responder
+    pub fn render(&self, messages: &[Message]) -> Result<String> {
+        let mut rendered = self.render_conversation(messages)?;
+        rendered.push_str(&self.start_completion);
+        Ok(rendered)
+    }
+
+    /// Prompt cuya respuesta empieza sin razonar, también en modo thinking
+    /// (p. ej. para clasificar por el primer token)
+    pub fn render_direct(&self, messages: &[Message]) -> Result<String> {
+        let mut rendered = self.render_conversation(messages)?;
+        rendered.push_str(&self.direct_completion);
+        Ok(rendered)
+    }
+
+    /// Conversación con el chat template, sin abrir un nuevo turno del asistente
+    pub fn render_conversation(&self, messages: &[Message]) -> Result<String> {
+        let text_messages: Vec<String> = messages
+            .iter()
+            .map(|message| match message {
+                Message::System { content } => self.render_message("system", content),
+                Message::User { content } => self.render_message("user", content),
+                Message::Assistant { content } => self.render_message("assistant", content),
+            })
+            .collect::<Result<Vec<String>>>()?;
+
+        Ok(text_messages.join(""))
+    }
+
+    fn render_message(&self, role: &str, message: &str) -> Result<String> {
+        let mut context = Context::new();
+        context.insert("message", message);
+        let msg = self.prompt.render(role, &context)?;
+        Ok(msg)
+    }
+}
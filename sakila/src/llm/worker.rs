@@ -0,0 +1,222 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, mpsc},
+    task::{Context, Poll},
+    thread,
+};
+
+use color_eyre::{Result, eyre::eyre};
+use futures_util::Stream;
+use tokenizers::Tokenizer;
+use tokio::sync::{
+    mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
+    oneshot,
+};
+
+use super::{
+    Completion, Llm,
+    stream::{Segment, TextDecoder, ThinkSplitter, TokenCallback},
+    template::ChatTemplate,
+};
+use crate::{cancel::CancelToken, chat::Message, config::SamplingOverride, t};
+
+/// Eventos de una generación en curso
+#[derive(Debug)]
+pub enum Event {
+    /// Texto nuevo listo para mostrar
+    TextDelta(String),
+    /// Razonamiento nuevo (modo thinking)
+    ThinkingDelta(String),
+    /// Fin de la generación, con el motivo y el uso de tokens
+    Finished(Completion),
+}
+
+enum Request {
+    Generate {
+        messages: Vec<Message>,
+        cancel: CancelToken,
+        sampling: SamplingOverride,
+        events: UnboundedSender<Result<Event>>,
+        /// Span de quien pide la generación: las trazas del hilo del modelo
+        /// cuelgan de él
+        span: tracing::Span,
+    },
+    Classify {
+        messages: Vec<Message>,
+        labels: Vec<String>,
+        reply: oneshot::Sender<Result<usize>>,
+        span: tracing::Span,
+    },
+}
+
+/// El modelo vive en un hilo dedicado: la inferencia (CPU/GPU intensiva)
+/// no bloquea el runtime de tokio y los tokens llegan por un canal.
+pub struct LlmWorker {
+    requests: mpsc::Sender<Request>,
+    tokenizer: Arc<Tokenizer>,
+    template: ChatTemplate,
+}
+
+impl LlmWorker {
+    pub fn spawn(mut llm: Llm) -> Result<Self> {
+        let tokenizer = llm.get_tokenizer();
+        let template = llm.get_template();
+        let (requests, receiver) = mpsc::channel::<Request>();
+
+        thread::Builder::new()
+            .name("llm".to_string())
+            .spawn(move || {
+                for request in receiver {
+                    match request {
+                        Request::Generate {
+                            messages,
+                            cancel,
+                            sampling,
+                            events,
+                            span,
+                        } => span.in_scope(|| {
+                            let (think_start, think_end) = llm.get_think_tags();
+                            let splitter = ThinkSplitter::new(think_start, think_end);
+                            let mut callback =
+                                ChannelCallback::new(llm.get_tokenizer(), splitter, events.clone());
+                            let result =
+                                llm.chat_stream(&messages, &mut callback, &cancel, &sampling);
+                            // Si el receptor ya no existe, nadie espera el resultado
+                            let _ = events.send(result.map(Event::Finished));
+                        }),
+                        Request::Classify {
+                            messages,
+                            labels,
+                            reply,
+                            span,
+                        } => span.in_scope(|| {
+                            let _ = reply.send(llm.classify(&messages, &labels));
+                        }),
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            requests,
+            tokenizer,
+            template,
+        })
+    }
+
+    pub fn get_tokenizer(&self) -> Arc<Tokenizer> {
+        self.tokenizer.clone()
+    }
+
+    pub fn get_template(&self) -> &ChatTemplate {
+        &self.template
+    }
+
+    /// Encola una generación y devuelve el stream de sus eventos. `sampling`
+    /// cambia los parámetros de muestreo solo para esta petición
+    pub fn generate(
+        &self,
+        messages: &[Message],
+        cancel: &CancelToken,
+        sampling: &SamplingOverride,
+    ) -> GenerationStream {
+        let (events, receiver) = unbounded_channel();
+        let request = Request::Generate {
+            messages: messages.to_vec(),
+            cancel: cancel.clone(),
+            sampling: *sampling,
+            events,
+            span: tracing::Span::current(),
+        };
+
+        if let Err(mpsc::SendError(Request::Generate { events, .. })) = self.requests.send(request)
+        {
+            let _ = events.send(Err(eyre!("{}", t!("llm.worker_stopped"))));
+        }
+
+        GenerationStream { receiver }
+    }
+
+    /// Elige una de las etiquetas como respuesta a los mensajes
+    pub async fn classify(&self, messages: &[Message], labels: &[String]) -> Result<usize> {
+        let (reply, receiver) = oneshot::channel();
+        let request = Request::Classify {
+            messages: messages.to_vec(),
+            labels: labels.to_vec(),
+            reply,
+            span: tracing::Span::current(),
+        };
+
+        self.requests
+            .send(request)
+            .map_err(|_| eyre!("{}", t!("llm.worker_stopped")))?;
+        receiver
+            .await
+            .map_err(|_| eyre!("{}", t!("llm.worker_stopped")))?
+    }
+}
+
+/// Stream de eventos de una generación
+pub struct GenerationStream {
+    receiver: UnboundedReceiver<Result<Event>>,
+}
+
+impl Stream for GenerationStream {
+    type Item = Result<Event>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Envía cada token y el texto decodificado por el canal de eventos
+struct ChannelCallback {
+    decoder: TextDecoder,
+    splitter: ThinkSplitter,
+    events: UnboundedSender<Result<Event>>,
+}
+
+impl ChannelCallback {
+    fn new(
+        tokenizer: Arc<Tokenizer>,
+        splitter: ThinkSplitter,
+        events: UnboundedSender<Result<Event>>,
+    ) -> Self {
+        Self {
+            decoder: TextDecoder::new(tokenizer),
+            splitter,
+            events,
+        }
+    }
+
+    fn send(&self, segments: Vec<Segment>) {
+        for segment in segments {
+            let event = match segment {
+                Segment::Thinking(text) => Event::ThinkingDelta(text),
+                Segment::Answer(text) => Event::TextDelta(text),
+            };
+            let _ = self.events.send(Ok(event));
+        }
+    }
+}
+
+impl TokenCallback for ChannelCallback {
+    fn on_token(&mut self, token_id: u32) {
+        if let Some(text) = self.decoder.push(token_id) {
+            let segments = self.splitter.push(&text);
+            self.send(segments);
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(text) = self.decoder.finish() {
+            let segments = self.splitter.push(&text);
+            self.send(segments);
+        }
+        let segments = self.splitter.finish();
+        self.send(segments);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.events.is_closed()
+    }
+}
@@ -1,20 +1,25 @@
-use crate::{agent::Agent, config::AppConfig};
+use crate::{
+    agent::Agent,
+    config::AppConfig,
+    i18n::Locale,
+    llm::worker::LlmWorker,
+    ui::{Frontend, repl::Repl},
+};
 use color_eyre::Result;
-use tracing_subscriber::EnvFilter;
 
 mod agent;
+mod cancel;
 mod chat;
 mod config;
 mod db;
 mod device;
+mod i18n;
 mod llm;
+mod telemetry;
+mod ui;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-
     color_eyre::install()?;
 
     rustls::crypto::aws_lc_rs::default_provider()
@@ -22,20 +27,68 @@ async fn main() -> Result<()> {
         .expect("Failed to install crypto provider");
 
     // Cargar configuración
-    let config = AppConfig::load()?;
+    let mut config = AppConfig::load()?;
+
+    // Idioma de los mensajes y de los prompts
+    i18n::init(Locale::detect(config.locale))?;
+
+    // Dataset propio: cargo run -- <fichero o directorio>; --tui para la TUI
+    let mut tui = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--tui" => tui = true,
+            _ => config.db.dataset = Some(arg),
+        }
+    }
+
+    // En la TUI los logs no pueden mezclarse con la pantalla
+    if tui && config.telemetry.file.is_none() {
+        if let Some(parent) = std::path::Path::new(&config.tui.log_file).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        config.telemetry.file = Some(config.tui.log_file.clone());
+    }
+
+    // Logs, spans y métricas
+    let _telemetry = telemetry::init(&config.telemetry)?;
 
     // Load database
     let db = db::load(&config.db).await?;
 
     // Load llm
     let llm = llm::Llm::load(&config).await?;
+    let llm = LlmWorker::spawn(llm)?;
 
-    // Build agent
-    let mut agent = Agent::new(&config.agent, llm, db);
+    let ui: Box<dyn Frontend> = if tui {
+        tui_frontend(&config)?
+    } else {
+        Box::new(Repl::new(config.agent.thinking_display))
+    };
 
-    println!("Sakila Chat (type /exit to quit)\n");
+    // Build agent
+    let mut agent = Agent::new(
+        &config.agent,
+        llm,
+        db,
+        &db::name(&config.db),
+        &db::fingerprint(&config.db),
+        ui,
+    )
+    .await?;
 
     agent.run().await?;
 
     Ok(())
 }
+
+#[cfg(feature = "tui")]
+fn tui_frontend(config: &AppConfig) -> Result<Box<dyn Frontend>> {
+    Ok(Box::new(ui::tui::Tui::new(&config.llm)))
+}
+
+#[cfg(not(feature = "tui"))]
+fn tui_frontend(_config: &AppConfig) -> Result<Box<dyn Frontend>> {
+    Err(color_eyre::eyre::eyre!(
+        "--tui requiere compilar con --features tui"
+    ))
+}
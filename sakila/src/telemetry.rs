@@ -0,0 +1,103 @@
+use std::{fs::File, sync::Mutex};
+
+use color_eyre::Result;
+use tracing_subscriber::{EnvFilter, Layer, fmt::format::FmtSpan, prelude::*};
+
+use crate::config::{TelemetryConfig, TelemetryFormat};
+
+/// Mantiene vivos los exportadores hasta el final del programa
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+pub fn init(cfg: &TelemetryConfig) -> Result<Telemetry> {
+    // Los spans se emiten al cerrarse, con su duración
+    let fmt_layer = match (&cfg.format, &cfg.file) {
+        (TelemetryFormat::Json, Some(file)) => tracing_subscriber::fmt::layer()
+            .json()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(Mutex::new(File::create(file)?))
+            .boxed(),
+        (TelemetryFormat::Json, None) => tracing_subscriber::fmt::layer()
+            .json()
+            .with_span_events(FmtSpan::CLOSE)
+            .boxed(),
+        (TelemetryFormat::Pretty, Some(file)) => tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(Mutex::new(File::create(file)?))
+            .boxed(),
+        (TelemetryFormat::Pretty, None) => tracing_subscriber::fmt::layer().boxed(),
+    };
+
+    let registry = tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(fmt_layer);
+
+    #[cfg(feature = "otlp")]
+    {
+        let provider = match &cfg.otlp_endpoint {
+            Some(endpoint) => Some(otlp::provider(endpoint)?),
+            None => None,
+        };
+        let otel_layer = provider.as_ref().map(otlp::layer);
+        registry.with(otel_layer).init();
+
+        Ok(Telemetry { provider })
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.init();
+        if cfg.otlp_endpoint.is_some() {
+            tracing::warn!("⚠️  otlp_endpoint requiere compilar con --features otlp");
+        }
+
+        Ok(Telemetry {})
+    }
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use color_eyre::Result;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{
+        Resource,
+        trace::{SdkTracerProvider, Tracer},
+    };
+    use tracing_opentelemetry::OpenTelemetryLayer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    pub fn provider(endpoint: &str) -> Result<SdkTracerProvider> {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()?;
+
+        Ok(SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("sakila").build())
+            .build())
+    }
+
+    pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, Tracer>
+    where
+        S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("sakila"))
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        // Enviar los spans pendientes antes de salir
+        if let Some(provider) = self.provider.take()
+            && let Err(err) = provider.shutdown()
+        {
+            eprintln!("Error al cerrar el exportador OTLP: {}", err);
+        }
+    }
+}
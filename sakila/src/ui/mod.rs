@@ -0,0 +1,67 @@
+use color_eyre::Result;
+use serde_json::Value;
+
+use crate::{cancel::CancelToken, llm::Usage};
+
+pub mod repl;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+/// Tipo de aviso que el agente muestra al usuario
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notice {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// Datos de la sesión que el frontend necesita al arrancar
+pub struct Session<'a> {
+    pub database: &'a str,
+    /// Tablas y columnas, una línea por tabla: `tabla(col tipo, ...)`
+    #[cfg_attr(not(feature = "tui"), allow(dead_code))]
+    pub schema: &'a str,
+    pub cancel: &'a CancelToken,
+}
+
+/// Interfaz de usuario del agente. El núcleo (memoria, SQL, auditoría...)
+/// es el mismo para el REPL de línea y para la TUI; solo cambia cómo se
+/// leen los mensajes y cómo se muestra cada evento.
+pub trait Frontend {
+    fn start(&mut self, session: &Session) -> Result<()>;
+
+    /// Siguiente mensaje o comando del usuario; `None` termina la sesión
+    fn read_input(&mut self) -> Result<Option<String>>;
+
+    /// Empieza una respuesta del modelo
+    fn assistant_start(&mut self) -> Result<()>;
+
+    /// Razonamiento del modo thinking
+    fn thinking_delta(&mut self, text: &str) -> Result<()>;
+
+    fn text_delta(&mut self, text: &str) -> Result<()>;
+
+    /// Fin de una generación, con los tokens y la velocidad
+    fn usage(&mut self, usage: &Usage) -> Result<()>;
+
+    fn sql_start(&mut self, sql: &str) -> Result<()>;
+
+    /// Resultado de una query: las filas y el texto que recibe el modelo
+    fn sql_result(&mut self, result: &Value, formatted: &str, cached: bool) -> Result<()>;
+
+    /// El modelo pide una aclaración antes de seguir
+    fn clarification(&mut self, question: &str, options: &[String]) -> Result<()>;
+
+    /// Gráfico en texto del último resultado
+    fn chart(&mut self, text: &str) -> Result<()>;
+
+    fn notice(&mut self, notice: Notice, text: &str) -> Result<()>;
+
+    /// Bloque de texto largo con título (p. ej. `/debug last`)
+    fn block(&mut self, title: &str, text: &str) -> Result<()>;
+
+    fn turn_finished(&mut self) -> Result<()>;
+
+    fn finish(&mut self) -> Result<()>;
+}
@@ -0,0 +1,172 @@
+use std::io::{Write, stdin, stdout};
+
+use color_eyre::Result;
+use colored::Colorize;
+use serde_json::Value;
+
+use super::{Frontend, Notice, Session};
+use crate::{config::ThinkingDisplay, llm::Usage, t};
+
+/// REPL de línea: lee de stdin y escribe en la terminal con colores
+pub struct Repl {
+    thinking_display: ThinkingDisplay,
+    /// El último texto fue razonamiento (para separarlo de la respuesta)
+    thinking: bool,
+}
+
+impl Repl {
+    pub fn new(thinking_display: ThinkingDisplay) -> Self {
+        Self {
+            thinking_display,
+            thinking: false,
+        }
+    }
+}
+
+impl Frontend for Repl {
+    fn start(&mut self, session: &Session) -> Result<()> {
+        session.cancel.listen_ctrl_c();
+
+        println!(
+            "{}",
+            format!("🎬 Sakila Agent · {}", session.database)
+                .bright_magenta()
+                .bold()
+        );
+        println!("{}\n", t!("repl.help").dimmed());
+        Ok(())
+    }
+
+    fn read_input(&mut self) -> Result<Option<String>> {
+        println!(
+            "{}",
+            format!("\n─ {} ─", t!("chat.you")).bright_cyan().bold()
+        );
+        stdout().flush()?;
+
+        let mut input = String::new();
+        if stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(input.trim().to_string()))
+    }
+
+    fn assistant_start(&mut self) -> Result<()> {
+        self.thinking = false;
+        println!(
+            "{}",
+            format!("\n─ {} ─", t!("chat.assistant"))
+                .bright_cyan()
+                .bold()
+        );
+        stdout().flush()?;
+        Ok(())
+    }
+
+    fn thinking_delta(&mut self, text: &str) -> Result<()> {
+        match self.thinking_display {
+            ThinkingDisplay::Dimmed => print!("{}", text.dimmed().italic()),
+            ThinkingDisplay::Hidden if !self.thinking => print!("{}", t!("repl.thinking").dimmed()),
+            ThinkingDisplay::Hidden => {}
+        }
+        self.thinking = true;
+        stdout().flush()?;
+        Ok(())
+    }
+
+    fn text_delta(&mut self, text: &str) -> Result<()> {
+        // Separar visualmente el razonamiento de la respuesta
+        let text = if self.thinking {
+            println!("\n");
+            self.thinking = false;
+            text.trim_start()
+        } else {
+            text
+        };
+        print!("{}", text);
+        stdout().flush()?;
+        Ok(())
+    }
+
+    fn usage(&mut self, usage: &Usage) -> Result<()> {
+        println!(
+            "{}",
+            format!(
+                "\n{}",
+                t!(
+                    "repl.usage",
+                    prompt = usage.prompt_tokens,
+                    generated = usage.generated_tokens,
+                    speed = format!("{:.1}", usage.tokens_per_sec),
+                    ttft = usage.ttft_ms
+                )
+            )
+            .dimmed()
+        );
+        Ok(())
+    }
+
+    fn sql_start(&mut self, sql: &str) -> Result<()> {
+        println!("\n");
+        println!("{}", t!("repl.running_sql").bright_yellow());
+        println!("   {}", sql.dimmed());
+        Ok(())
+    }
+
+    fn sql_result(&mut self, _result: &Value, formatted: &str, cached: bool) -> Result<()> {
+        if cached {
+            println!("{}", t!("repl.cached").bright_green());
+        } else {
+            println!("{}", t!("repl.executed").bright_green());
+        }
+        println!("{}", formatted.dimmed());
+        Ok(())
+    }
+
+    fn clarification(&mut self, question: &str, options: &[String]) -> Result<()> {
+        println!("\n");
+        println!("{}", format!("❓ {}", question).bright_magenta().bold());
+        for (i, option) in options.iter().enumerate() {
+            println!("{}", format!("   {}. {}", i + 1, option).bright_magenta());
+        }
+        let hint = if options.is_empty() {
+            t!("repl.clarify_free")
+        } else {
+            t!("repl.clarify_options")
+        };
+        println!("{}", hint.dimmed());
+        Ok(())
+    }
+
+    fn chart(&mut self, text: &str) -> Result<()> {
+        println!("{}", text.bright_blue());
+        Ok(())
+    }
+
+    fn notice(&mut self, notice: Notice, text: &str) -> Result<()> {
+        let text = match notice {
+            Notice::Info => text.dimmed(),
+            Notice::Success => text.bright_green(),
+            Notice::Warning => text.bright_yellow(),
+            Notice::Error => text.bright_red(),
+        };
+        println!("{}", text);
+        Ok(())
+    }
+
+    fn block(&mut self, title: &str, text: &str) -> Result<()> {
+        println!("{}", format!("─ {} ─", title).bright_cyan().bold());
+        println!("{}", text);
+        Ok(())
+    }
+
+    fn turn_finished(&mut self) -> Result<()> {
+        println!();
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        println!("{}", t!("repl.bye").bright_yellow());
+        Ok(())
+    }
+}
@@ -0,0 +1,559 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+};
+
+use color_eyre::{Result, eyre::eyre};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Cell, Paragraph, Row, Table, TableState, Wrap},
+};
+use serde_json::Value;
+
+use super::{Frontend, Notice, Session};
+use crate::{config::LlmConfig, llm::Usage, t};
+
+/// Ancho máximo de una columna de la tabla de resultados
+const MAX_COLUMN_WIDTH: usize = 30;
+
+/// Tipo de cada bloque del chat, del que sale su estilo
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Header,
+    User,
+    Answer,
+    Thinking,
+    Sql,
+    Chart,
+    Clarify,
+    Notice(Notice),
+}
+
+struct Entry {
+    kind: Kind,
+    text: String,
+}
+
+/// Resultado de la última query, navegable fila a fila
+struct ResultTable {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    cached: bool,
+}
+
+/// Interfaz a pantalla completa: chat, esquema, resultados y barra de estado.
+/// Un hilo lee el teclado: así Ctrl-C cancela aunque el agente esté ocupado
+/// ejecutando una query o generando.
+pub struct Tui {
+    terminal: Option<DefaultTerminal>,
+    keys: Option<Receiver<KeyEvent>>,
+    busy: Arc<AtomicBool>,
+    model: String,
+    context_length: usize,
+    database: String,
+    schema: Vec<(String, Vec<String>)>,
+    schema_scroll: u16,
+    chat: Vec<Entry>,
+    /// Líneas desplazadas hacia arriba desde el final del chat
+    chat_scroll: u16,
+    input: String,
+    result: Option<ResultTable>,
+    table: TableState,
+    last_sql: Option<String>,
+    usage: Option<Usage>,
+}
+
+impl Tui {
+    pub fn new(llm: &LlmConfig) -> Self {
+        Self {
+            terminal: None,
+            keys: None,
+            busy: Arc::new(AtomicBool::new(false)),
+            model: llm.file.clone(),
+            context_length: llm.context_length,
+            database: String::new(),
+            schema: Vec::new(),
+            schema_scroll: 0,
+            chat: Vec::new(),
+            chat_scroll: 0,
+            input: String::new(),
+            result: None,
+            table: TableState::default(),
+            last_sql: None,
+            usage: None,
+        }
+    }
+
+    /// Añade texto al último bloque si es del mismo tipo (streaming)
+    fn append(&mut self, kind: Kind, text: &str) {
+        match self.chat.last_mut() {
+            Some(entry) if entry.kind == kind => entry.text.push_str(text),
+            _ => self.push(kind, text),
+        }
+    }
+
+    fn push(&mut self, kind: Kind, text: &str) {
+        self.chat.push(Entry {
+            kind,
+            text: text.to_string(),
+        });
+        self.chat_scroll = 0;
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let Some(mut terminal) = self.terminal.take() else {
+            return Ok(());
+        };
+        let drawn = terminal.draw(|frame| self.render(frame)).map(|_| ());
+        self.terminal = Some(terminal);
+        drawn?;
+        Ok(())
+    }
+
+    /// Atiende las teclas pendientes sin bloquear (mientras el agente trabaja)
+    fn poll_keys(&mut self) -> Result<()> {
+        loop {
+            let key = match self.keys.as_ref().map(|keys| keys.try_recv()) {
+                Some(Ok(key)) => key,
+                Some(Err(TryRecvError::Empty)) | None => break,
+                Some(Err(TryRecvError::Disconnected)) => {
+                    return Err(eyre!("Se perdió la lectura del teclado"));
+                }
+            };
+            self.navigate(&key);
+        }
+        self.draw()
+    }
+
+    /// Teclas de navegación, válidas también durante una respuesta
+    fn navigate(&mut self, key: &KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::PageUp => self.chat_scroll = self.chat_scroll.saturating_add(10),
+            KeyCode::PageDown => self.chat_scroll = self.chat_scroll.saturating_sub(10),
+            KeyCode::Up if ctrl => self.schema_scroll = self.schema_scroll.saturating_sub(1),
+            KeyCode::Down if ctrl => self.schema_scroll = self.schema_scroll.saturating_add(1),
+            KeyCode::Up => self.table.select_previous(),
+            KeyCode::Down => self.table.select_next(),
+            KeyCode::Char(c) if !ctrl => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [main, input, status] = Layout::vertical([
+            Constraint::Min(5),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [schema, right] =
+            Layout::horizontal([Constraint::Percentage(25), Constraint::Percentage(75)])
+                .areas(main);
+        let [chat, results] =
+            Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(right);
+
+        self.render_schema(frame, schema);
+        self.render_chat(frame, chat);
+        self.render_results(frame, results);
+
+        let prompt =
+            Paragraph::new(self.input.as_str()).block(Block::bordered().title(t!("tui.input")));
+        frame.render_widget(prompt, input);
+        let cursor_x = input.x + 1 + self.input.chars().count() as u16;
+        frame.set_cursor_position((cursor_x.min(input.right().saturating_sub(2)), input.y + 1));
+
+        frame.render_widget(
+            Paragraph::new(self.status_line()).style(Style::new().bg(Color::DarkGray)),
+            status,
+        );
+    }
+
+    fn render_schema(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        for (table, columns) in &self.schema {
+            lines.push(Line::styled(
+                format!("▸ {}", table),
+                Style::new().add_modifier(Modifier::BOLD),
+            ));
+            for (i, column) in columns.iter().enumerate() {
+                let branch = if i + 1 == columns.len() { "└" } else { "├" };
+                lines.push(Line::styled(
+                    format!("  {} {}", branch, column),
+                    Style::new().fg(Color::Gray),
+                ));
+            }
+        }
+
+        let title = format!(" {} ", self.database);
+        let schema = Paragraph::new(lines)
+            .block(Block::bordered().title(title))
+            .scroll((self.schema_scroll, 0));
+        frame.render_widget(schema, area);
+    }
+
+    fn render_chat(&self, frame: &mut Frame, area: Rect) {
+        let mut lines: Vec<Line> = Vec::new();
+        for entry in &self.chat {
+            let style = match &entry.kind {
+                Kind::Header => Style::new().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+                Kind::User => Style::new().fg(Color::White),
+                Kind::Answer => Style::new(),
+                Kind::Thinking => Style::new()
+                    .fg(Color::DarkGray)
+                    .add_modifier(Modifier::ITALIC),
+                Kind::Sql => Style::new().fg(Color::Yellow),
+                Kind::Chart => Style::new().fg(Color::Blue),
+                Kind::Clarify => Style::new().fg(Color::Magenta),
+                Kind::Notice(Notice::Info) => Style::new().fg(Color::DarkGray),
+                Kind::Notice(Notice::Success) => Style::new().fg(Color::Green),
+                Kind::Notice(Notice::Warning) => Style::new().fg(Color::Yellow),
+                Kind::Notice(Notice::Error) => Style::new().fg(Color::Red),
+            };
+            for line in entry.text.trim_matches('\n').lines() {
+                lines.push(Line::styled(line.to_string(), style));
+            }
+        }
+
+        // Se muestra el final del chat salvo que el usuario haya subido
+        let width = area.width.saturating_sub(2).max(1) as usize;
+        let height: usize = lines
+            .iter()
+            .map(|line| line.width().div_ceil(width).max(1))
+            .sum();
+        let visible = area.height.saturating_sub(2) as usize;
+        let bottom = height.saturating_sub(visible) as u16;
+        let offset = bottom.saturating_sub(self.chat_scroll);
+
+        let chat = Paragraph::new(Text::from(lines))
+            .block(Block::bordered().title(t!("tui.chat")))
+            .wrap(Wrap { trim: false })
+            .scroll((offset, 0));
+        frame.render_widget(chat, area);
+    }
+
+    fn render_results(&mut self, frame: &mut Frame, area: Rect) {
+        let Some(result) = &self.result else {
+            frame.render_widget(Block::bordered().title(t!("tui.results")), area);
+            return;
+        };
+
+        let widths: Vec<Constraint> = result
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let width = result
+                    .rows
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([column.chars().count()])
+                    .max()
+                    .unwrap_or(1)
+                    .min(MAX_COLUMN_WIDTH);
+                Constraint::Length(width as u16)
+            })
+            .collect();
+
+        let header = Row::new(result.columns.iter().map(|c| Cell::from(c.as_str())))
+            .style(Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+        let rows = result
+            .rows
+            .iter()
+            .map(|row| Row::new(row.iter().map(|cell| Cell::from(cell.as_str()))));
+
+        let position = self.table.selected().map_or(0, |i| i + 1);
+        let title = t!(
+            "tui.results_rows",
+            shown = position,
+            total = result.rows.len(),
+            cached = if result.cached {
+                t!("tui.results_cached")
+            } else {
+                ""
+            }
+        );
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(Block::bordered().title(title))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn status_line(&self) -> Line<'static> {
+        let state = if self.busy.load(Ordering::SeqCst) {
+            t!("tui.busy")
+        } else {
+            t!("tui.ready")
+        };
+        let usage = match &self.usage {
+            Some(usage) => {
+                let context = usage.prompt_tokens + usage.generated_tokens;
+                t!(
+                    "tui.context",
+                    speed = format!("{:.1}", usage.tokens_per_sec),
+                    used = context,
+                    total = self.context_length,
+                    percent = format!(
+                        "{:.0}",
+                        context as f64 * 100.0 / self.context_length.max(1) as f64
+                    )
+                )
+            }
+            None => t!("tui.context_empty", total = self.context_length),
+        };
+
+        Line::from(vec![
+            Span::styled(
+                format!(" {} ", self.model),
+                Style::new().fg(Color::Magenta).add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("· {} · {} ", usage, state)),
+            Span::styled(t!("tui.keys"), Style::new().fg(Color::Gray)),
+        ])
+    }
+
+    fn set_busy(&mut self, busy: bool) -> Result<()> {
+        self.busy.store(busy, Ordering::SeqCst);
+        self.poll_keys()
+    }
+}
+
+impl Frontend for Tui {
+    fn start(&mut self, session: &Session) -> Result<()> {
+        self.database = session.database.to_string();
+        self.schema = session.schema.lines().filter_map(parse_table).collect();
+
+        // Lectura del teclado en su propio hilo. Ctrl-C cancela la operación
+        // en curso; sin operación, llega a read_input y cierra la sesión
+        let (sender, receiver) = mpsc::channel();
+        let busy = self.busy.clone();
+        let cancel = session.cancel.clone();
+        thread::Builder::new()
+            .name("tui-keys".to_string())
+            .spawn(move || {
+                while let Ok(event) = event::read() {
+                    let Event::Key(key) = event else {
+                        // Redimensionar: basta con volver a dibujar
+                        if sender.send(KeyEvent::from(KeyCode::Null)).is_err() {
+                            break;
+                        }
+                        continue;
+                    };
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    let ctrl_c = key.code == KeyCode::Char('c')
+                        && key.modifiers.contains(KeyModifiers::CONTROL);
+                    if ctrl_c && busy.load(Ordering::SeqCst) {
+                        cancel.cancel();
+                        continue;
+                    }
+                    if sender.send(key).is_err() {
+                        break;
+                    }
+                }
+            })?;
+
+        self.keys = Some(receiver);
+        self.terminal = Some(ratatui::init());
+        self.push(Kind::Notice(Notice::Info), t!("tui.welcome"));
+        self.draw()
+    }
+
+    fn read_input(&mut self) -> Result<Option<String>> {
+        self.busy.store(false, Ordering::SeqCst);
+        loop {
+            self.draw()?;
+            let Some(keys) = self.keys.as_ref() else {
+                return Ok(None);
+            };
+            let Ok(key) = keys.recv() else {
+                return Ok(None);
+            };
+
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c' | 'd') if ctrl => return Ok(None),
+                KeyCode::Char('r') if ctrl => {
+                    if self.last_sql.is_some() {
+                        self.push(Kind::User, "/rerun");
+                        return Ok(Some("/rerun".to_string()));
+                    }
+                }
+                KeyCode::Char('e') if ctrl => {
+                    if let Some(sql) = &self.last_sql {
+                        self.input = format!("/sql {}", sql);
+                    }
+                }
+                KeyCode::Enter if !self.input.trim().is_empty() => {
+                    let input = std::mem::take(&mut self.input);
+                    self.push(Kind::Header, &format!("─ {} ─", t!("chat.you")));
+                    self.push(Kind::User, &input);
+                    return Ok(Some(input));
+                }
+                _ => {
+                    self.navigate(&key);
+                }
+            }
+        }
+    }
+
+    fn assistant_start(&mut self) -> Result<()> {
+        self.push(Kind::Header, &format!("─ {} ─", t!("chat.assistant")));
+        self.set_busy(true)
+    }
+
+    fn thinking_delta(&mut self, text: &str) -> Result<()> {
+        self.append(Kind::Thinking, text);
+        self.poll_keys()
+    }
+
+    fn text_delta(&mut self, text: &str) -> Result<()> {
+        let text = match self.chat.last() {
+            Some(entry) if entry.kind != Kind::Answer => text.trim_start(),
+            _ => text,
+        };
+        self.append(Kind::Answer, text);
+        self.poll_keys()
+    }
+
+    fn usage(&mut self, usage: &Usage) -> Result<()> {
+        self.usage = Some(*usage);
+        self.poll_keys()
+    }
+
+    fn sql_start(&mut self, sql: &str) -> Result<()> {
+        self.last_sql = Some(sql.to_string());
+        self.push(Kind::Sql, &format!("🔍 {}", sql));
+        self.set_busy(true)
+    }
+
+    fn sql_result(&mut self, result: &Value, _formatted: &str, cached: bool) -> Result<()> {
+        let rows = result["rows"].as_array().cloned().unwrap_or_default();
+        let columns: Vec<String> = rows
+            .first()
+            .and_then(|row| row.as_object())
+            .map(|row| row.keys().cloned().collect())
+            .unwrap_or_default();
+        let rows: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|column| cell_text(&row[column]))
+                    .collect()
+            })
+            .collect();
+
+        let notice = if cached {
+            t!("tui.cached", rows = rows.len())
+        } else {
+            t!("tui.executed", rows = rows.len())
+        };
+        self.push(Kind::Notice(Notice::Success), &notice);
+
+        self.table = TableState::default().with_selected((!rows.is_empty()).then_some(0));
+        self.result = Some(ResultTable {
+            columns,
+            rows,
+            cached,
+        });
+        self.poll_keys()
+    }
+
+    fn clarification(&mut self, question: &str, options: &[String]) -> Result<()> {
+        let mut text = format!("❓ {}", question);
+        for (i, option) in options.iter().enumerate() {
+            text.push_str(&format!("\n   {}. {}", i + 1, option));
+        }
+        self.push(Kind::Clarify, &text);
+        self.poll_keys()
+    }
+
+    fn chart(&mut self, text: &str) -> Result<()> {
+        self.push(Kind::Chart, text);
+        self.poll_keys()
+    }
+
+    fn notice(&mut self, notice: Notice, text: &str) -> Result<()> {
+        self.push(Kind::Notice(notice), text.trim());
+        self.poll_keys()
+    }
+
+    fn block(&mut self, title: &str, text: &str) -> Result<()> {
+        self.push(Kind::Header, &format!("─ {} ─", title));
+        self.push(Kind::Answer, text);
+        self.poll_keys()
+    }
+
+    fn turn_finished(&mut self) -> Result<()> {
+        self.set_busy(false)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.terminal.take().is_some() {
+            ratatui::restore();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        // También si la sesión termina con un error
+        if self.terminal.take().is_some() {
+            ratatui::restore();
+        }
+    }
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// `tabla(col tipo, col tipo)` -> (tabla, columnas). Las comas dentro de
+/// paréntesis (p. ej. `DECIMAL(4,2)`) no separan columnas
+fn parse_table(line: &str) -> Option<(String, Vec<String>)> {
+    let (table, rest) = line.split_once('(')?;
+    let rest = rest.strip_suffix(')')?;
+
+    let mut columns = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    for c in rest.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                columns.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        columns.push(current.trim().to_string());
+    }
+
+    Some((table.trim().to_string(), columns))
+}